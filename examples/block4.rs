@@ -2,7 +2,7 @@ use std::{
     io,
     io::IsTerminal,
     io::Write,
-    process::{Command, Output},
+    process::{Command, Output, Stdio},
 };
 
 /// Alias for our `Result` type. You could also use `anyhow` instead.
@@ -38,6 +38,13 @@ mod builtins {
             Self { dir }
         }
 
+        /// Create a `Cd` command which changes into the home directory.
+        /// This is what `cd` without arguments does.
+        pub fn home() -> Result<Self> {
+            let dir = std::env::home_dir().ok_or("cd: HOME not set")?;
+            Ok(Self { dir })
+        }
+
         /// Run the `cd` command.
         pub fn run(self) -> Result<Option<Output>> {
             // `std::env::set_current_dir` changes the current directory of the process
@@ -96,8 +103,11 @@ mod builtins {
             Self { history_path }
         }
 
-        /// Add a command to the history.
+        /// Add a command to the history. Blank lines are not added.
         pub fn add(&self, command: &str) -> Result<()> {
+            if command.trim().is_empty() {
+                return Ok(());
+            }
             let mut history = std::fs::OpenOptions::new()
                 .create(true)
                 .append(true)
@@ -286,11 +296,10 @@ impl Element {
 impl Cmd {
     fn run(self) -> Option<Output> {
         let result = match self.binary.as_ref() {
-            "cd" => {
-                let dir = self.args.first()?;
-                let dir = std::path::PathBuf::from(dir);
-                builtins::Cd::new(dir).run()
-            }
+            "cd" => match self.args.first() {
+                Some(dir) => builtins::Cd::new(std::path::PathBuf::from(dir)).run(),
+                None => builtins::Cd::home().and_then(|cd| cd.run()),
+            },
             "exit" => {
                 let status = match self.args.first() {
                     Some(status) => status.parse().unwrap_or(0),
@@ -320,10 +329,23 @@ impl Cmd {
         None
     }
 
+    /// Run an external command and wait until it exits.
+    ///
+    /// The command inherits the stdin, stdout and stderr of the shell, so its output
+    /// appears while it runs, and interactive programs like `vim` or `less` work.
+    /// Nothing is captured, so the `Output` only contains the exit status.
     fn run_external(self) -> Result<Option<Output>> {
-        let child = Command::new(self.binary).args(self.args).spawn()?;
-        let output = child.wait_with_output().expect("command wasn't running");
-        Ok(Some(output))
+        let status = Command::new(self.binary)
+            .args(self.args)
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .status()?;
+        Ok(Some(Output {
+            status,
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        }))
     }
 }
 
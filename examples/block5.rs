@@ -1,6 +1,8 @@
+// The parser is in `src/parser.rs`, so that other programs can use it too.
+use rush::{parse, Chain, Cmd, Element};
 use std::{
     io::{self, IsTerminal, Write},
-    process::{Command, Output, Stdio},
+    process::{Child, ChildStdout, Command, Output, Stdio},
 };
 
 /// Alias for our `Result` type. You could also use `anyhow` instead.
@@ -94,8 +96,11 @@ mod builtins {
             Self { history_path }
         }
 
-        /// Add a command to the history.
+        /// Add a command to the history. Blank lines are not added.
         pub fn add(&self, command: &str) -> Result<()> {
+            if command.trim().is_empty() {
+                return Ok(());
+            }
             let mut history = std::fs::OpenOptions::new()
                 .create(true)
                 .append(true)
@@ -123,9 +128,16 @@ fn main() {
         show_prompt();
         let line = read_line();
         history.add(line.trim()).expect("Cannot open history file");
-        let chains = chains_from_line(line);
+        let chains = match parse(&line) {
+            Ok(chains) => chains,
+            Err(e) => {
+                // Nothing of a malformed line runs, just like in other shells.
+                eprintln!("Error: {}", e);
+                continue;
+            }
+        };
         for chain in chains {
-            let output = chain.run();
+            let output = run_chain(chain);
             if let Some(output) = output {
                 std::io::stdout().write_all(&output.stdout).unwrap();
             }
@@ -153,267 +165,185 @@ fn read_line() -> String {
     line
 }
 
-fn chains_from_line(line: String) -> Vec<Chain> {
-    // For simplicity's sake, this workshop uses the split function.
-    // This is inefficient because it parses the whole line.
-    // If you feel adventurous, try to parse the line character by character instead. 🤠
-    line.split(';')
-        .filter_map(|s| Parser::new(s).parse())
-        .collect()
-}
-
-// This struct doesn't use lifetimes to keep the code simple.
-// You can try to use `&str` instead of `String`
-// to avoid unnecessary allocations. 👍
-#[derive(PartialEq, Debug)]
-struct Cmd {
-    binary: String,
-    args: Vec<String>,
-}
-
-#[derive(PartialEq, Debug)]
-enum Element {
-    /// `|`
-    Pipe,
-    /// `&&`
-    And,
-    /// `||`
-    Or,
-    /// Command.
-    Cmd(Cmd),
-}
-
-/// Parse `[Element]`s from a string.
-struct Parser {
-    current: usize,
-    tokens: Vec<String>,
-}
-
-impl Parser {
-    fn new(chain: &str) -> Self {
-        Self {
-            tokens: chain.split_whitespace().map(String::from).collect(),
-            current: 0,
-        }
-    }
-
-    fn parse(&mut self) -> Option<Chain> {
-        let mut elements = vec![];
-        while let Some(e) = self.parse_next() {
-            elements.push(e);
-        }
-        if elements.is_empty() {
-            None
-        } else {
-            Some(Chain { elements })
-        }
-    }
-
-    fn parse_next(&mut self) -> Option<Element> {
-        let next = self.tokens.get(self.current).map(|s| s.to_string());
-        next.and_then(|next| {
-            self.current += 1;
-            Element::parse_operator(&next).or_else(|| self.parse_cmd(next).map(Element::Cmd))
-        })
-    }
-
-    fn parse_cmd(&mut self, binary: String) -> Option<Cmd> {
-        let mut args: Vec<String> = vec![];
-        loop {
-            let next = self.tokens.get(self.current);
-            match next {
-                Some(token) if Element::is_operator(token) => {
-                    // found operator, so I already parsed all cmd
+/// Run the pipelines of `chain`, depending on the exit status of the previous one.
+fn run_chain(chain: Chain) -> Option<Output> {
+    let mut output = None;
+    // The commands of the current pipeline, e.g. `ls` and `wc` in `ls | wc && echo done`.
+    let mut pipeline = vec![];
+    for e in chain.elements {
+        match e {
+            Element::Cmd(cmd) => pipeline.push(cmd),
+            Element::Pipe => continue,
+            Element::And => {
+                output = run_pipeline(std::mem::take(&mut pipeline));
+                if !output.as_ref()?.status.success() {
                     break;
                 }
-                Some(token) => {
-                    args.push(token.to_string());
+            }
+            Element::Or => {
+                output = run_pipeline(std::mem::take(&mut pipeline));
+                if output.as_ref()?.status.success() {
+                    break;
                 }
-                None => break,
             }
-            self.current += 1;
         }
-        Some(Cmd { binary, args })
     }
+    if !pipeline.is_empty() {
+        output = run_pipeline(pipeline);
+    }
+    output
 }
 
-#[derive(PartialEq, Debug)]
-struct Chain {
-    elements: Vec<Element>,
-}
-
-impl Chain {
-    fn run(self) -> Option<Output> {
-        let mut prev_output: Option<Output> = None;
-        for e in self.elements {
-            match e {
-                Element::Cmd(cmd) => {
-                    prev_output = cmd.run(prev_output);
-                }
-                Element::Pipe => continue,
-                Element::And => {
-                    if !prev_output.as_ref()?.status.success() {
-                        break;
-                    }
-                }
-                Element::Or => {
-                    if prev_output.as_ref()?.status.success() {
-                        break;
-                    }
-                }
-            }
+/// Run the commands of a pipeline, connecting the stdout of each to the stdin of the next.
+///
+/// External commands run at the same time, connected by pipes of the operating system.
+/// This way, the data streams through the pipeline instead of being collected by the shell,
+/// and `yes | head -n 1` stops once `head` is done.
+/// Only the output of the last command is collected, so `main` can print it.
+fn run_pipeline(cmds: Vec<Cmd>) -> Option<Output> {
+    let mut children = vec![];
+    let mut input = None;
+    let mut last = None;
+    for (i, cmd) in cmds.iter().enumerate() {
+        let stage = run_cmd(cmd, input.take());
+        if i + 1 == cmds.len() {
+            last = stage;
+            break;
         }
-        prev_output
+        input = Some(match stage {
+            Some(Stage::Done(output)) => PipeInput::Bytes(output.stdout),
+            Some(Stage::Running(mut child)) => {
+                let stdout = child.stdout.take().expect("stdout is piped");
+                children.push(child);
+                PipeInput::Child(stdout)
+            }
+            // Commands like `cd` have no output, so the next command reads nothing.
+            None => PipeInput::Bytes(Vec::new()),
+        });
     }
-}
 
-impl Element {
-    fn parse_operator(token: &str) -> Option<Self> {
-        match token {
-            "|" => Some(Self::Pipe),
-            "&&" => Some(Self::And),
-            "||" => Some(Self::Or),
-            _ => None,
+    let output = match last {
+        Some(Stage::Done(output)) => Some(output),
+        Some(Stage::Running(child)) => child
+            .wait_with_output()
+            .map_err(|e| eprintln!("Error: {}", e))
+            .ok(),
+        None => None,
+    };
+    // Wait for the other commands too, otherwise they would stay around as zombies.
+    for mut child in children {
+        if let Err(e) = child.wait() {
+            eprintln!("Error: {}", e);
         }
     }
+    output
+}
 
-    fn is_operator(token: &str) -> bool {
-        Self::parse_operator(token).is_some()
-    }
+/// The stdin of the next command in a pipeline.
+enum PipeInput {
+    /// The output of a command which is done, e.g. a builtin.
+    Bytes(Vec<u8>),
+    /// The stdout of a command which is still running.
+    Child(ChildStdout),
 }
 
-impl Cmd {
-    fn run(&self, prev_output: Option<Output>) -> Option<Output> {
-        let result = match self.binary.as_ref() {
-            "cd" => {
-                let dir = self.args.first()?;
-                let dir = std::path::PathBuf::from(dir);
-                builtins::Cd::new(dir).run()
-            }
-            "exit" => {
-                let status = self.args.first().and_then(|s| s.parse().ok()).unwrap_or(0);
-                builtins::Exit::new(status).run()
-            }
-            "history" => builtins::History::new().run(),
-            _ => self.run_external(prev_output),
-        };
+/// A command of a pipeline which was started.
+enum Stage {
+    /// Builtins are done once they return.
+    Done(Output),
+    /// External commands run at the same time as the rest of the pipeline.
+    Running(Child),
+}
 
-        match result {
-            Ok(output) => {
-                if let Some(output) = &output {
-                    // Print stderr (if any)
-                    std::io::stderr().write_all(&output.stderr).unwrap();
-                }
-                output
-            }
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                None
-            }
+/// Start `cmd`, or run it if it is a builtin.
+fn run_cmd(cmd: &Cmd, input: Option<PipeInput>) -> Option<Stage> {
+    let result = match cmd.binary {
+        "cd" => {
+            let dir = cmd.args.first()?;
+            let dir = std::path::PathBuf::from(dir);
+            builtins::Cd::new(dir)
+                .run()
+                .map(|output| output.map(Stage::Done))
         }
-    }
-
-    fn run_external(&self, prev_output: Option<Output>) -> Result<Option<Output>> {
-        let mut command = Command::new(&self.binary);
-        command.args(&self.args);
-
-        if prev_output.is_some() {
-            command.stdin(Stdio::piped());
+        "exit" => {
+            let status = cmd.args.first().and_then(|s| s.parse().ok()).unwrap_or(0);
+            builtins::Exit::new(status)
+                .run()
+                .map(|output| output.map(Stage::Done))
         }
-
-        let mut child = command
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()?;
-
-        if let Some(prev_output) = prev_output {
-            if let Some(mut stdin) = child.stdin.take() {
-                stdin.write_all(&prev_output.stdout)?;
+        "history" => builtins::History::new()
+            .run()
+            .map(|output| output.map(Stage::Done)),
+        _ => run_external(cmd, input).map(Some),
+    };
+
+    match result {
+        Ok(stage) => {
+            if let Some(Stage::Done(output)) = &stage {
+                // Print stderr (if any)
+                std::io::stderr().write_all(&output.stderr).unwrap();
             }
+            stage
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            None
         }
-
-        let output = child.wait_with_output()?;
-        Ok(Some(output))
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    fn parse_chains(line: &str) -> Vec<Chain> {
-        chains_from_line(line.to_string())
-    }
-
-    #[test]
-    fn no_cmd_is_parsed_from_empty_line() {
-        assert_eq!(parse_chains(""), vec![]);
-    }
-
-    #[test]
-    fn cmd_with_no_args_is_parsed() {
-        assert_eq!(
-            parse_chains("ls"),
-            vec![Chain {
-                elements: vec![Element::Cmd(Cmd {
-                    binary: "ls".to_string(),
-                    args: vec![]
-                }),]
-            },]
-        );
-    }
-
-    #[test]
-    fn cmd_with_args_is_parsed() {
-        assert_eq!(
-            parse_chains("ls -l"),
-            vec![Chain {
-                elements: vec![Element::Cmd(Cmd {
-                    binary: "ls".to_string(),
-                    args: vec!["-l".to_string()]
-                })]
-            }]
-        );
-    }
-
-    #[test]
-    fn cmds_are_parsed() {
-        assert_eq!(
-            parse_chains("ls; echo hello"),
-            vec![
-                Chain {
-                    elements: vec![Element::Cmd(Cmd {
-                        binary: "ls".to_string(),
-                        args: vec![]
-                    }),]
-                },
-                Chain {
-                    elements: vec![Element::Cmd(Cmd {
-                        binary: "echo".to_string(),
-                        args: vec!["hello".to_string()]
-                    }),]
-                },
-            ]
-        );
-    }
-
-    #[test]
-    fn pipe_is_parsed() {
-        assert_eq!(
-            parse_chains("ls | wc -l"),
-            vec![Chain {
-                elements: vec![
-                    Element::Cmd(Cmd {
-                        binary: "ls".to_string(),
-                        args: vec![]
-                    }),
-                    Element::Pipe,
-                    Element::Cmd(Cmd {
-                        binary: "wc".to_string(),
-                        args: vec!["-l".to_string()]
-                    }),
-                ]
-            }]
-        );
+/// Start an external command. It reads from `input`, if it is part of a pipeline.
+///
+/// The command keeps running, see [`run_pipeline`]. Its stderr goes directly to the
+/// stderr of the shell, while its stdout is piped to the next command.
+fn run_external(cmd: &Cmd, input: Option<PipeInput>) -> Result<Stage> {
+    let mut command = Command::new(cmd.binary);
+    command.args(&cmd.args).stdout(Stdio::piped());
+
+    let bytes = match input {
+        // Connect the commands directly, so the shell doesn't see the data at all.
+        Some(PipeInput::Child(stdout)) => {
+            command.stdin(stdout);
+            None
+        }
+        Some(PipeInput::Bytes(bytes)) => {
+            command.stdin(Stdio::piped());
+            Some(bytes)
+        }
+        None => None,
+    };
+
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        // Report a missing binary with an empty output instead of an error,
+        // so the next command of a pipeline reads an empty stdin instead of
+        // waiting for the terminal.
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            #[cfg(unix)]
+            use std::os::unix::process::ExitStatusExt;
+            #[cfg(windows)]
+            use std::os::windows::process::ExitStatusExt;
+
+            // On Unix, the raw status stores the exit code in the second byte.
+            #[cfg(unix)]
+            let status = std::process::ExitStatus::from_raw(127 << 8);
+            #[cfg(windows)]
+            let status = std::process::ExitStatus::from_raw(127);
+
+            return Ok(Stage::Done(Output {
+                status,
+                stdout: Vec::new(),
+                stderr: format!("{}: command not found\n", cmd.binary).into_bytes(),
+            }));
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    if let (Some(bytes), Some(mut stdin)) = (bytes, child.stdin.take()) {
+        // Write from another thread. The command may produce output before it
+        // has read all of its input, and we must not block it while doing so.
+        // Errors mean that the command doesn't read its input, which is fine.
+        std::thread::spawn(move || stdin.write_all(&bytes));
     }
+    Ok(Stage::Running(child))
 }
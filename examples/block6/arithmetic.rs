@@ -0,0 +1,149 @@
+//! Arithmetic expansion: `$((2 + 3 * 4))` is replaced with `14`.
+//!
+//! The expression is evaluated by a small recursive descent parser.
+//! Each precedence level has its own function, the lowest level comes first:
+//!
+//! ```text
+//! sum     = product (('+' | '-') product)*
+//! product = unary (('*' | '/' | '%') unary)*
+//! unary   = ('+' | '-') unary | '(' sum ')' | number | name
+//! ```
+//!
+//! Names are variables. Like in bash, unset or empty variables are 0.
+
+use crate::{variables::Variables, Result};
+use std::{iter::Peekable, str::Chars};
+
+/// Evaluate the integer expression between `$((` and `))`.
+pub fn evaluate(expression: &str, variables: &Variables) -> Result<i64> {
+    let mut chars = expression.chars().peekable();
+    let value = sum(&mut chars, variables)?;
+    skip_whitespace(&mut chars);
+    match chars.next() {
+        None => Ok(value),
+        Some(c) => Err(format!("{expression}: syntax error near `{c}`").into()),
+    }
+}
+
+fn sum(chars: &mut Peekable<Chars>, variables: &Variables) -> Result<i64> {
+    let mut value = product(chars, variables)?;
+    loop {
+        skip_whitespace(chars);
+        match chars.next_if(|&c| c == '+' || c == '-') {
+            Some('+') => value = value.wrapping_add(product(chars, variables)?),
+            Some(_) => value = value.wrapping_sub(product(chars, variables)?),
+            None => return Ok(value),
+        }
+    }
+}
+
+fn product(chars: &mut Peekable<Chars>, variables: &Variables) -> Result<i64> {
+    let mut value = unary(chars, variables)?;
+    loop {
+        skip_whitespace(chars);
+        let Some(operator) = chars.next_if(|&c| matches!(c, '*' | '/' | '%')) else {
+            return Ok(value);
+        };
+        let operand = unary(chars, variables)?;
+        value = match operator {
+            '*' => value.wrapping_mul(operand),
+            _ if operand == 0 => return Err("division by zero".into()),
+            '/' => value.wrapping_div(operand),
+            _ => value.wrapping_rem(operand),
+        };
+    }
+}
+
+fn unary(chars: &mut Peekable<Chars>, variables: &Variables) -> Result<i64> {
+    skip_whitespace(chars);
+    match chars.next() {
+        Some('+') => unary(chars, variables),
+        Some('-') => Ok(unary(chars, variables)?.wrapping_neg()),
+        Some('(') => {
+            let value = sum(chars, variables)?;
+            skip_whitespace(chars);
+            match chars.next() {
+                Some(')') => Ok(value),
+                _ => Err("missing `)`".into()),
+            }
+        }
+        Some(c) if c.is_ascii_digit() => {
+            let mut digits = c.to_string();
+            digits.extend(std::iter::from_fn(|| chars.next_if(char::is_ascii_digit)));
+            digits
+                .parse()
+                .map_err(|_| format!("{digits}: number too large").into())
+        }
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            let mut name = c.to_string();
+            name.extend(std::iter::from_fn(|| {
+                chars.next_if(|&c| c.is_ascii_alphanumeric() || c == '_')
+            }));
+            variable(&name, variables)
+        }
+        Some(c) => Err(format!("syntax error near `{c}`").into()),
+        None => Err("operand expected".into()),
+    }
+}
+
+/// The value of the variable `name` as a number.
+fn variable(name: &str, variables: &Variables) -> Result<i64> {
+    let value = variables.get(name).unwrap_or_default();
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(0);
+    }
+    value
+        .parse()
+        .map_err(|_| format!("{name}: {value}: not a number").into())
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(expression: &str) -> Result<i64> {
+        evaluate(expression, &Variables::new())
+    }
+
+    #[test]
+    fn multiplication_binds_stronger_than_addition() {
+        assert_eq!(eval("2 + 3 * 4").unwrap(), 14);
+        assert_eq!(eval("10 - 4 / 2 - 1").unwrap(), 7);
+        assert_eq!(eval("7 % 4 * 2").unwrap(), 6);
+    }
+
+    #[test]
+    fn parentheses_are_evaluated_first() {
+        assert_eq!(eval("(2 + 3) * 4").unwrap(), 20);
+        assert_eq!(eval("-(1 - (2 - 3))").unwrap(), -2);
+    }
+
+    #[test]
+    fn division_by_zero_is_an_error() {
+        assert!(eval("1 / 0").is_err());
+        assert!(eval("1 % (2 - 2)").is_err());
+    }
+
+    #[test]
+    fn variables_are_numbers() {
+        let mut variables = Variables::new();
+        variables.set("ARITHMETIC_TEST_VAR", "5");
+        assert_eq!(evaluate("ARITHMETIC_TEST_VAR * 2", &variables).unwrap(), 10);
+        assert_eq!(
+            evaluate("ARITHMETIC_TEST_UNSET + 1", &variables).unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn invalid_expressions_are_errors() {
+        assert!(eval("1 +").is_err());
+        assert!(eval("(1").is_err());
+        assert!(eval("1 2").is_err());
+    }
+}
@@ -0,0 +1,2677 @@
+//! The built-in commands of the shell.
+//!
+//! In the earlier blocks this module lived next to the rest of the code.
+//! Now that the shell keeps growing, it gets its own file.
+
+use crate::{
+    completion::Completions,
+    jobs::Jobs as JobTable,
+    parser::{is_name, read_continued_lines, read_here_documents, Cmd, Redirect},
+    shell::{Options, Shell},
+    variables::Variables,
+    PipeInput, Result,
+};
+use std::io::{BufRead, BufReader, IsTerminal, Read, Seek, SeekFrom, Write};
+use std::{
+    cell::Cell,
+    cmp::Ordering,
+    collections::{HashMap, VecDeque},
+    path::{Component, Path, PathBuf},
+    process::{ExitStatus, Output},
+};
+
+/// Special builtins, as defined by POSIX.
+///
+/// Variables assigned before a special builtin, like `FOO=bar exec`,
+/// stay set in the shell. For all other commands, they are only set
+/// while the command runs.
+pub const SPECIAL_BUILTINS: &[&str] = &[
+    "break", ":", ".", "continue", "eval", "exec", "exit", "export", "readonly", "return", "set",
+    "shift", "times", "trap", "unset",
+];
+
+/// A command which runs in the shell itself.
+pub struct Builtin {
+    pub name: &'static str,
+    /// A short description, which `help` prints.
+    pub description: &'static str,
+    /// Run the command. Builtins which read their input get it with [`Cmd::builtin_stdin`].
+    pub run: fn(&Cmd, &mut Option<PipeInput>, &mut Shell) -> Result<Option<Output>>,
+}
+
+/// All builtins. Commands with these names run in the shell itself.
+///
+/// The shell runs builtins from this list, `help` prints it and `type` uses it to recognize
+/// builtins. So a new builtin only needs to be added here.
+pub const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "cd",
+        description: "Change the current directory",
+        run: |cmd, _, shell| Cd::new(&cmd.args)?.run(&mut shell.previous_dir),
+    },
+    Builtin {
+        name: "pushd",
+        description: "Save the current directory and change to another one",
+        run: |cmd, _, shell| {
+            Pushd::new(&cmd.args)?.run(&mut shell.previous_dir, &mut shell.dir_stack)
+        },
+    },
+    Builtin {
+        name: "popd",
+        description: "Change back to the last directory saved by `pushd`",
+        run: |cmd, _, shell| {
+            Popd::new(&cmd.args)?.run(&mut shell.previous_dir, &mut shell.dir_stack)
+        },
+    },
+    Builtin {
+        name: "dirs",
+        description: "Show the directory stack",
+        run: |_, _, shell| Dirs::new().run(&shell.dir_stack),
+    },
+    Builtin {
+        name: "bookmark",
+        description: "Save the current directory for `cd @name`",
+        run: |cmd, _, _| Bookmark::new(&cmd.args)?.run(&Bookmarks::new()),
+    },
+    Builtin {
+        name: "exit",
+        description: "Exit the shell",
+        run: |cmd, _, shell| {
+            Exit::new(&cmd.args, shell.last_status.unwrap_or(0))?.run(&mut shell.jobs)
+        },
+    },
+    Builtin {
+        name: "logout",
+        description: "Exit a login shell",
+        run: |_, _, shell| {
+            Logout::new(crate::is_login_shell(), shell.last_status.unwrap_or(0))
+                .run(&mut shell.jobs)
+        },
+    },
+    Builtin {
+        name: "history",
+        description: "Show the command history, search it with --search or print it as --json",
+        run: |cmd, _, _| match cmd.args.as_slice() {
+            [] => History::new().run(None),
+            [flag] if flag == "-c" => History::new().clear(),
+            [flag, term] if flag == "--search" => History::new().search(term),
+            [flag] if flag == "--search" => Err("history: --search: argument required".into()),
+            [flag] if flag == "--json" => History::new().json(),
+            [count] => match count.parse() {
+                Ok(count) => History::new().run(Some(count)),
+                Err(_) => Err(format!("history: {count}: numeric argument required").into()),
+            },
+            _ => Err("history: too many arguments".into()),
+        },
+    },
+    Builtin {
+        name: "echo",
+        description: "Print the arguments",
+        run: |cmd, _, _| Echo::new(&cmd.args).run(),
+    },
+    Builtin {
+        name: "read",
+        description: "Read a line from stdin into variables",
+        run: |cmd, input, shell| {
+            let stdin = cmd.builtin_stdin(input)?;
+            ReadLine::new(&cmd.args)?.run(stdin, &mut shell.variables)
+        },
+    },
+    Builtin {
+        name: "env",
+        description: "Run a command in a modified environment",
+        // With a command, `env` starts it like an external command, see `Cmd::command`.
+        run: |cmd, _, _| Env::new(&cmd.args)?.run(),
+    },
+    Builtin {
+        name: "export",
+        description: "Set environment variables or list them",
+        run: |cmd, _, shell| Export::new(&cmd.args).run(&mut shell.variables),
+    },
+    Builtin {
+        name: "printenv",
+        description: "Print environment variables",
+        run: |cmd, _, _| Printenv::new(cmd.args.clone()).run(),
+    },
+    Builtin {
+        name: "alias",
+        description: "Define or show aliases",
+        run: |cmd, _, shell| Alias::new(cmd.args.clone()).run(&mut shell.aliases),
+    },
+    Builtin {
+        name: "complete",
+        description: "Register the words the first argument of a command completes to",
+        run: |cmd, _, shell| Complete::new(&cmd.args)?.run(&mut shell.completions),
+    },
+    Builtin {
+        name: "shopt",
+        description: "Set and unset shell options",
+        run: |cmd, _, shell| Shopt::new(cmd.args.clone()).run(&mut shell.jobs),
+    },
+    Builtin {
+        name: "set",
+        description:
+            "Turn shell options on with -e, -x and -o pipefail, and off with +e, +x and +o",
+        run: |cmd, _, shell| Set::new(&cmd.args)?.run(&mut shell.options),
+    },
+    Builtin {
+        name: "jobs",
+        description: "List the background jobs",
+        run: |_, _, shell| Jobs::new().run(&shell.jobs),
+    },
+    Builtin {
+        name: "disown",
+        description: "Remove a job from the job table",
+        run: |cmd, _, shell| Disown::new(&cmd.args)?.run(&mut shell.jobs),
+    },
+    Builtin {
+        name: "wait",
+        description: "Wait for background jobs to finish",
+        run: |cmd, _, shell| Wait::new(&cmd.args)?.run(&mut shell.jobs),
+    },
+    Builtin {
+        name: "exec",
+        description: "Replace the shell with a command or redirect its output",
+        run: |cmd, _, _| Exec::new(cmd.args.clone(), cmd.redirects.clone()).run(),
+    },
+    Builtin {
+        name: "source",
+        description: "Run the commands of a file in the current shell",
+        run: |cmd, _, shell| Source::new(&cmd.args)?.run(|line| shell.run_line(line)),
+    },
+    Builtin {
+        name: ".",
+        description: "Same as `source`",
+        run: |cmd, _, shell| Source::new(&cmd.args)?.run(|line| shell.run_line(line)),
+    },
+    Builtin {
+        name: "sort",
+        description: "Sort lines of text",
+        run: |cmd, input, _| {
+            let stdin = cmd.builtin_stdin(input)?;
+            Sort::new(&cmd.args)?.run(stdin)
+        },
+    },
+    Builtin {
+        name: "head",
+        description: "Print the first lines of the input",
+        run: |cmd, input, _| {
+            let stdin = cmd.builtin_stdin(input)?;
+            Head::new(&cmd.args)?.run(stdin)
+        },
+    },
+    Builtin {
+        name: "tail",
+        description: "Print the last lines of the input",
+        run: |cmd, input, _| {
+            let stdin = cmd.builtin_stdin(input)?;
+            Tail::new(&cmd.args)?.run(stdin)
+        },
+    },
+    Builtin {
+        name: "tr",
+        description: "Translate or delete characters",
+        run: |cmd, input, _| {
+            let stdin = cmd.builtin_stdin(input)?;
+            Tr::new(&cmd.args)?.run(stdin)
+        },
+    },
+    Builtin {
+        name: "type",
+        description: "Show how a command name is resolved",
+        run: |cmd, _, _| Type::new(cmd.args.clone()).run(),
+    },
+    Builtin {
+        name: "repeat",
+        description: "Run a command several times",
+        run: |cmd, _, shell| {
+            Repeat::new(&cmd.args)?.run(|words| {
+                // The words are already expanded, so the command runs without expanding them again.
+                let cmd = Cmd {
+                    assignments: Vec::new(),
+                    binary: words[0].clone(),
+                    args: words[1..].to_vec(),
+                    redirects: Vec::new(),
+                };
+                cmd.run_to_completion(shell)
+            })
+        },
+    },
+    Builtin {
+        name: "true",
+        description: "Do nothing, successfully",
+        run: |_, _, _| True.run(),
+    },
+    Builtin {
+        name: "false",
+        description: "Do nothing, unsuccessfully",
+        run: |_, _, _| False.run(),
+    },
+    Builtin {
+        name: "clear",
+        description: "Clear the terminal",
+        run: |_, _, _| Clear.run(),
+    },
+    Builtin {
+        name: "help",
+        description: "Show this list",
+        run: |_, _, _| Help.run(),
+    },
+];
+
+/// Keywords with a short description. They are part of the syntax of the shell, not commands.
+///
+/// `help` prints them together with the builtins and `type` recognizes them.
+pub const KEYWORDS: &[(&str, &str)] = &[("time", "Report how long a pipeline takes")];
+
+/// The builtin called `name`, if there is one.
+pub fn find_builtin(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+/// Whether `name` is a keyword, like `time`.
+pub fn is_keyword(name: &str) -> bool {
+    KEYWORDS.iter().any(|&(keyword, _)| keyword == name)
+}
+
+/// The `cd` command changes the current directory.
+///
+/// The `cd` command changes the current directory of the shell.
+/// If the directory is not found, it prints an error message.
+/// `cd @name` changes into the directory saved with `bookmark name`.
+/// If the directory is successfully changed, it returns `Ok(())` and
+/// the shell should update its current directory.
+///
+/// `cd -` goes back to the previous directory and prints it.
+/// `cd` without a directory goes to the home directory.
+/// A directory which isn't found is searched in the directories of `$CDPATH`,
+/// and the directory found there is printed.
+///
+/// By default (`-L`), `cd` follows the logical path: `cd link/..` goes back to
+/// where we came from, even if `link` is a symbolic link to another place.
+/// With `-P`, symbolic links are resolved first, so `$PWD` is the physical path.
+pub struct Cd {
+    /// The directory to change into.
+    dir: PathBuf,
+    /// `-P`: resolve symbolic links.
+    physical: bool,
+    /// `cd -`: go back to the previous directory.
+    back: bool,
+}
+
+impl Cd {
+    /// Create a new `Cd` command from its arguments.
+    pub fn new(args: &[String]) -> Result<Self> {
+        let mut physical = false;
+        let mut args = args.iter().peekable();
+        // `-` on its own is a directory, not an option.
+        let mut end_of_options = false;
+        while let Some(flag) = args.next_if(|arg| arg.starts_with('-') && arg.len() > 1) {
+            // After `--`, everything is a path, even `-` or `-P`.
+            if flag == "--" {
+                end_of_options = true;
+                break;
+            }
+            for c in flag[1..].chars() {
+                match c {
+                    'L' => physical = false,
+                    'P' => physical = true,
+                    _ => return Err(format!("cd: -{c}: invalid option").into()),
+                }
+            }
+        }
+        let dir = match args.next() {
+            Some(dir) => PathBuf::from(dir),
+            None => std::env::home_dir().ok_or("cd: HOME not set")?,
+        };
+        if args.next().is_some() {
+            return Err("cd: too many arguments".into());
+        }
+        let back = !end_of_options && dir.as_os_str() == "-";
+        Ok(Self {
+            dir,
+            physical,
+            back,
+        })
+    }
+
+    /// Run the `cd` command.
+    ///
+    /// `previous_dir` is the directory before the last `cd`.
+    /// It is updated whenever the directory changes.
+    pub fn run(self, previous_dir: &mut Option<PathBuf>) -> Result<Option<Output>> {
+        let name = self.dir.clone();
+        let back = self.back;
+        let bookmark = self.dir.to_str().and_then(|dir| dir.strip_prefix('@'));
+        let from_cdpath = search_cdpath(&self.dir);
+        let dir = if back {
+            previous_dir.clone().ok_or("cd: OLDPWD not set")?
+        } else if let Some(name) = bookmark {
+            Bookmarks::new()
+                .get(name)?
+                .ok_or_else(|| format!("cd: @{name}: no such bookmark"))?
+        } else if let Some(dir) = &from_cdpath {
+            dir.clone()
+        } else {
+            self.dir
+        };
+        // The current directory may have been deleted, then there is nothing to go back to.
+        let current = logical_current_dir();
+        let dir = if self.physical {
+            dir.canonicalize().map_err(|e| cd_error(&name, e))?
+        } else {
+            logical_path(current.as_deref(), &dir)
+        };
+        // `std::env::set_current_dir` changes the current directory of the process
+        // (our shell in this case).
+        std::env::set_current_dir(&dir).map_err(|e| cd_error(&name, e))?;
+        // Programs like `pwd -L` read the logical path from `$PWD`.
+        std::env::set_var("PWD", &dir);
+        if let Some(current) = &current {
+            std::env::set_var("OLDPWD", current);
+        }
+        *previous_dir = current;
+
+        if back || from_cdpath.is_some() {
+            // Show the user where they ended up.
+            let stdout = format!("{}\n", dir.display());
+            return Ok(Some(output(0, stdout.into_bytes())));
+        }
+        // The `cd` command doesn't produce any output.
+        Ok(None)
+    }
+}
+
+/// The error for changing into `dir`, with the messages of bash for the common cases
+/// instead of the raw OS error.
+fn cd_error(dir: &Path, error: std::io::Error) -> Box<dyn std::error::Error> {
+    let reason = match error.kind() {
+        std::io::ErrorKind::NotFound => "no such file or directory",
+        std::io::ErrorKind::NotADirectory => "not a directory",
+        _ => return error.into(),
+    };
+    format!("cd: {reason}: {}", dir.display()).into()
+}
+
+/// Find `dir` in one of the directories of `$CDPATH`, like `cd` does.
+///
+/// `$CDPATH` is only searched if `dir` isn't found in the current directory
+/// and doesn't start with `/`, `.` or `..`.
+fn search_cdpath(dir: &Path) -> Option<PathBuf> {
+    let explicit = matches!(
+        dir.components().next(),
+        Some(Component::RootDir | Component::Prefix(_) | Component::CurDir | Component::ParentDir)
+    );
+    if explicit || dir.is_dir() {
+        return None;
+    }
+    let cdpath = std::env::var_os("CDPATH")?;
+    std::env::split_paths(&cdpath)
+        // An empty entry is the current directory, which was already tried.
+        .filter(|base| !base.as_os_str().is_empty())
+        .map(|base| base.join(dir))
+        .find(|candidate| candidate.is_dir())
+}
+
+/// The `pushd` command saves the current directory on the directory stack
+/// and changes to another directory.
+pub struct Pushd {
+    /// The directory to change into.
+    dir: PathBuf,
+}
+
+impl Pushd {
+    /// Create a new `Pushd` command from its arguments.
+    pub fn new(args: &[String]) -> Result<Self> {
+        match args {
+            [dir] => Ok(Self {
+                dir: PathBuf::from(dir),
+            }),
+            [] => Err("pushd: no other directory".into()),
+            _ => Err("pushd: too many arguments".into()),
+        }
+    }
+
+    /// Run the `pushd` command.
+    ///
+    /// Like `cd`, it updates `previous_dir`. The new stack is printed, like with `dirs`.
+    pub fn run(
+        self,
+        previous_dir: &mut Option<PathBuf>,
+        dir_stack: &mut Vec<PathBuf>,
+    ) -> Result<Option<Output>> {
+        let current = logical_current_dir().ok_or("pushd: current directory not found")?;
+        let cd = Cd {
+            dir: self.dir,
+            physical: false,
+            back: false,
+        };
+        cd.run(previous_dir)?;
+        dir_stack.push(current);
+        Dirs::new().run(dir_stack)
+    }
+}
+
+/// The `popd` command removes the last directory from the directory stack
+/// and changes back to it.
+pub struct Popd;
+
+impl Popd {
+    /// Create a new `Popd` command from its arguments.
+    pub fn new(args: &[String]) -> Result<Self> {
+        if !args.is_empty() {
+            return Err("popd: too many arguments".into());
+        }
+        Ok(Self)
+    }
+
+    /// Run the `popd` command.
+    pub fn run(
+        self,
+        previous_dir: &mut Option<PathBuf>,
+        dir_stack: &mut Vec<PathBuf>,
+    ) -> Result<Option<Output>> {
+        let dir = dir_stack.last().ok_or("popd: directory stack empty")?;
+        let cd = Cd {
+            dir: dir.clone(),
+            physical: false,
+            back: false,
+        };
+        cd.run(previous_dir)?;
+        // Only forget the directory once we are back in it.
+        dir_stack.pop();
+        Dirs::new().run(dir_stack)
+    }
+}
+
+/// The `dirs` command prints the directory stack.
+///
+/// The current directory comes first, followed by the saved directories,
+/// the most recent one first.
+pub struct Dirs;
+
+impl Dirs {
+    /// Create a new `Dirs` command.
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Run the `dirs` command.
+    pub fn run(self, dir_stack: &[PathBuf]) -> Result<Option<Output>> {
+        let current = logical_current_dir().ok_or("dirs: current directory not found")?;
+        let dirs: Vec<_> = std::iter::once(&current)
+            .chain(dir_stack.iter().rev())
+            .map(|dir| dir.display().to_string())
+            .collect();
+        let stdout = format!("{}\n", dirs.join(" "));
+        Ok(Some(output(0, stdout.into_bytes())))
+    }
+}
+
+/// Name of the bookmarks file in the user's home directory.
+const BOOKMARKS_FILE_NAME: &str = ".bookmarks";
+
+/// The bookmarked directories, stored in a file with one `name<TAB>path` per line.
+///
+/// Unlike the history, the file is not in the current directory:
+/// it has to be found again after changing directories.
+pub struct Bookmarks {
+    path: PathBuf,
+}
+
+impl Bookmarks {
+    /// Open the bookmarks of the user.
+    pub fn new() -> Self {
+        // The path can be overridden by setting the `BOOKMARKS_PATH` environment variable.
+        let path = std::env::var_os("BOOKMARKS_PATH")
+            .map(PathBuf::from)
+            .or_else(|| std::env::home_dir().map(|home| home.join(BOOKMARKS_FILE_NAME)))
+            .unwrap_or_else(|| PathBuf::from(BOOKMARKS_FILE_NAME));
+        Self { path }
+    }
+
+    /// Get all bookmarks, in the order they were first saved.
+    pub fn entries(&self) -> Result<Vec<(String, PathBuf)>> {
+        let bookmarks = match std::fs::read_to_string(&self.path) {
+            Ok(bookmarks) => bookmarks,
+            // No bookmarks file means nothing was bookmarked yet.
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(bookmarks
+            .lines()
+            .filter_map(|line| line.split_once('\t'))
+            .map(|(name, dir)| (name.to_string(), PathBuf::from(dir)))
+            .collect())
+    }
+
+    /// Get the directory saved as `name`.
+    pub fn get(&self, name: &str) -> Result<Option<PathBuf>> {
+        let entries = self.entries()?;
+        Ok(entries
+            .into_iter()
+            .find(|(bookmark, _)| bookmark == name)
+            .map(|(_, dir)| dir))
+    }
+
+    /// Save `dir` as `name`, replacing an earlier bookmark with the same name.
+    pub fn set(&self, name: &str, dir: &Path) -> Result<()> {
+        let mut entries = self.entries()?;
+        match entries.iter_mut().find(|(bookmark, _)| bookmark == name) {
+            Some(entry) => entry.1 = dir.to_path_buf(),
+            None => entries.push((name.to_string(), dir.to_path_buf())),
+        }
+        let mut file = std::fs::File::create(&self.path)?;
+        for (name, dir) in entries {
+            writeln!(file, "{name}\t{}", dir.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// The `bookmark` command saves the current directory under a name.
+///
+/// `bookmark name` saves it, `cd @name` changes back into it later.
+/// Without a name, all bookmarks are listed.
+pub struct Bookmark {
+    name: Option<String>,
+}
+
+impl Bookmark {
+    /// Create a new `Bookmark` command from its arguments.
+    pub fn new(args: &[String]) -> Result<Self> {
+        match args {
+            [] => Ok(Self { name: None }),
+            [name] if name.is_empty() || name.contains(['\t', '/']) => {
+                Err(format!("bookmark: {name}: invalid name").into())
+            }
+            [name] => Ok(Self {
+                name: Some(name.clone()),
+            }),
+            _ => Err("bookmark: too many arguments".into()),
+        }
+    }
+
+    /// Run the `bookmark` command.
+    pub fn run(self, bookmarks: &Bookmarks) -> Result<Option<Output>> {
+        let Some(name) = self.name else {
+            let mut stdout = Vec::new();
+            for (name, dir) in bookmarks.entries()? {
+                writeln!(stdout, "@{name}\t{}", dir.display())?;
+            }
+            return Ok(Some(output(0, stdout)));
+        };
+        let current = logical_current_dir().ok_or("bookmark: current directory not found")?;
+        bookmarks.set(&name, &current)?;
+        Ok(None)
+    }
+}
+
+/// The current directory, as the user reached it, i.e. without resolving symbolic links.
+///
+/// This is `$PWD` if it still points to the current directory.
+fn logical_current_dir() -> Option<PathBuf> {
+    let physical = std::env::current_dir().ok()?;
+    let logical = std::env::var_os("PWD")
+        .map(PathBuf::from)
+        .filter(|logical| {
+            logical.is_absolute() && logical.canonicalize().ok() == physical.canonicalize().ok()
+        });
+    Some(logical.unwrap_or(physical))
+}
+
+/// Join `dir` to `current` and remove `.` and `..` without looking at the file system.
+/// This way, `..` goes back through a symbolic link instead of to the link's parent.
+fn logical_path(current: Option<&Path>, dir: &Path) -> PathBuf {
+    let Some(current) = current else {
+        return dir.to_path_buf();
+    };
+    let mut path = PathBuf::new();
+    for component in current.join(dir).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                path.pop();
+            }
+            component => path.push(component),
+        }
+    }
+    path
+}
+
+/// The `echo` command prints its arguments, separated by spaces.
+///
+/// - `-n`: don't print the trailing newline.
+/// - `-s SEP`: separate the arguments with `SEP` instead of a space.
+///
+/// Options are only recognized before the first argument, and `--` ends them.
+/// This way, `echo -- -s` prints `-s`.
+pub struct Echo {
+    /// `-n`: don't print a newline at the end.
+    no_newline: bool,
+    /// `-s`: the text between two arguments.
+    separator: String,
+    args: Vec<String>,
+}
+
+impl Echo {
+    /// Create a new `Echo` command from its arguments.
+    pub fn new(args: &[String]) -> Self {
+        let mut echo = Self {
+            no_newline: false,
+            separator: " ".to_string(),
+            args: Vec::new(),
+        };
+        let mut rest = args;
+        loop {
+            match rest {
+                [flag, tail @ ..] if flag == "-n" => {
+                    echo.no_newline = true;
+                    rest = tail;
+                }
+                [flag, separator, tail @ ..] if flag == "-s" => {
+                    echo.separator = separator.clone();
+                    rest = tail;
+                }
+                [flag, tail @ ..] if flag == "--" => {
+                    rest = tail;
+                    break;
+                }
+                _ => break,
+            }
+        }
+        echo.args = rest.to_vec();
+        echo
+    }
+
+    /// Run the `echo` command.
+    pub fn run(self) -> Result<Option<Output>> {
+        let mut stdout = self.args.join(&self.separator);
+        if !self.no_newline {
+            stdout.push('\n');
+        }
+        Ok(Some(output(0, stdout.into_bytes())))
+    }
+}
+
+/// The `env` command runs a command in a modified environment.
+///
+/// `env FOO=bar command` runs `command` with `FOO` set to `bar`.
+/// With `-i`, the command starts with an empty environment instead,
+/// so only the given variables are set. Without a command, `env` prints
+/// the environment the command would get.
+pub struct Env {
+    /// `-i`: don't inherit the environment of the shell.
+    ignore_environment: bool,
+    vars: Vec<(String, String)>,
+    /// The command to run and its arguments.
+    command: Vec<String>,
+}
+
+impl Env {
+    /// Create a new `Env` command from its arguments.
+    pub fn new(args: &[String]) -> Result<Self> {
+        let mut env = Self {
+            ignore_environment: false,
+            vars: Vec::new(),
+            command: Vec::new(),
+        };
+        let mut args = args.iter().peekable();
+        while let Some(flag) = args.next_if(|arg| arg.starts_with('-')) {
+            match flag.as_str() {
+                "-i" | "-" => env.ignore_environment = true,
+                _ => return Err(format!("env: invalid option -- '{flag}'").into()),
+            }
+        }
+        while let Some(var) = args.next_if(|arg| arg.contains('=')) {
+            let (name, value) = var.split_once('=').unwrap_or_default();
+            env.vars.push((name.to_string(), value.to_string()));
+        }
+        env.command = args.cloned().collect();
+        Ok(env)
+    }
+
+    /// The command to run in the modified environment, if any.
+    pub fn command(&self) -> Option<std::process::Command> {
+        let (binary, args) = self.command.split_first()?;
+        let mut command = std::process::Command::new(binary);
+        if self.ignore_environment {
+            command.env_clear();
+        }
+        command.args(args).envs(self.vars.iter().cloned());
+        Some(command)
+    }
+
+    /// Run the `env` command without a command, printing the environment.
+    pub fn run(self) -> Result<Option<Output>> {
+        let mut vars: Vec<(String, String)> = if self.ignore_environment {
+            Vec::new()
+        } else {
+            std::env::vars().collect()
+        };
+        for (name, value) in self.vars {
+            match vars.iter_mut().find(|(existing, _)| *existing == name) {
+                Some((_, existing)) => *existing = value,
+                None => vars.push((name, value)),
+            }
+        }
+
+        let mut stdout = Vec::new();
+        for (name, value) in vars {
+            writeln!(stdout, "{name}={value}")?;
+        }
+        Ok(Some(output(0, stdout)))
+    }
+}
+
+/// The `export` command exports variables, so that they are passed to commands.
+///
+/// `export NAME=value OTHER=value` sets and exports each variable, a bare `NAME` exports
+/// the variable with its current value. Without arguments or with `-p`,
+/// all exported variables are listed in a form which can be run again.
+/// Invalid names are reported one by one, without skipping the valid assignments.
+pub struct Export {
+    args: Vec<String>,
+}
+
+impl Export {
+    /// Create a new `Export` command from its arguments.
+    pub fn new(args: &[String]) -> Self {
+        let args = match args {
+            [flag, rest @ ..] if flag == "-p" => rest,
+            args => args,
+        };
+        Self {
+            args: args.to_vec(),
+        }
+    }
+
+    /// Run the `export` command.
+    pub fn run(self, variables: &mut Variables) -> Result<Option<Output>> {
+        if !self.args.is_empty() {
+            let mut stderr = Vec::new();
+            let mut code = 0;
+            for arg in self.args {
+                let (name, value) = match arg.split_once('=') {
+                    Some((name, value)) => (name, Some(value)),
+                    None => (arg.as_str(), None),
+                };
+                if !is_name(name) {
+                    writeln!(stderr, "export: `{arg}': not a valid identifier")?;
+                    code = 1;
+                } else {
+                    variables.export(name, value);
+                }
+            }
+            let mut output = output(code, Vec::new());
+            output.stderr = stderr;
+            return Ok(Some(output));
+        }
+
+        let mut vars: Vec<_> = std::env::vars_os().collect();
+        vars.sort();
+        let mut stdout = Vec::new();
+        for (name, value) in vars {
+            // Quote the value, so it stays one word. A `'` ends the quote, is escaped and starts it again.
+            let value = value.to_string_lossy().replace('\'', r"'\''");
+            writeln!(stdout, "export {}='{value}'", name.to_string_lossy())?;
+        }
+        Ok(Some(output(0, stdout)))
+    }
+}
+
+/// The `printenv` command prints the values of environment variables.
+///
+/// Without names, all variables are printed like `NAME=value`, just like `env` does.
+/// It fails if one of the names isn't set.
+pub struct Printenv {
+    names: Vec<String>,
+}
+
+impl Printenv {
+    /// Create a new `Printenv` command.
+    pub fn new(names: Vec<String>) -> Self {
+        Self { names }
+    }
+
+    /// Run the `printenv` command.
+    pub fn run(self) -> Result<Option<Output>> {
+        if self.names.is_empty() {
+            return Env::new(&[])?.run();
+        }
+        let mut stdout = Vec::new();
+        let mut code = 0;
+        for name in self.names {
+            match std::env::var_os(name) {
+                Some(value) => writeln!(stdout, "{}", value.to_string_lossy())?,
+                None => code = 1,
+            }
+        }
+        Ok(Some(output(code, stdout)))
+    }
+}
+
+/// The `type` command shows how a command name would be resolved.
+///
+/// Builtins are found first, then executables in the directories of `$PATH`.
+pub struct Type {
+    names: Vec<String>,
+}
+
+impl Type {
+    /// Create a new `Type` command.
+    pub fn new(args: Vec<String>) -> Self {
+        Self { names: args }
+    }
+
+    /// Run the `type` command. It fails if any of the names is not found.
+    pub fn run(self) -> Result<Option<Output>> {
+        let path = std::env::var_os("PATH").unwrap_or_default();
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let mut code = 0;
+        for name in self.names {
+            if is_keyword(&name) {
+                writeln!(stdout, "{name} is a shell keyword")?;
+            } else if find_builtin(&name).is_some() {
+                writeln!(stdout, "{name} is a shell builtin")?;
+            } else if let Some(found) = find_in_path(&name, &path) {
+                writeln!(stdout, "{name} is {}", found.display())?;
+            } else {
+                writeln!(stderr, "type: {name}: not found")?;
+                code = 1;
+            }
+        }
+        Ok(Some(Output {
+            stderr,
+            ..output(code, stdout)
+        }))
+    }
+}
+
+/// Search the directories of `path` for an executable called `name`.
+/// Names containing a `/` are paths already, so they aren't searched.
+fn find_in_path(name: &str, path: &std::ffi::OsStr) -> Option<PathBuf> {
+    if name.contains('/') {
+        let path = PathBuf::from(name);
+        return is_executable(&path).then_some(path);
+    }
+    std::env::split_paths(path)
+        .map(|dir| dir.join(name))
+        .find(|candidate| is_executable(candidate))
+}
+
+/// Whether `path` is a file which may be executed.
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+/// Whether `path` is a file which may be executed.
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// The `true` command does nothing, successfully.
+///
+/// It is often used in conditions, like `true && echo yes`.
+/// The builtin also works on platforms without a `true` binary, like Windows.
+pub struct True;
+
+impl True {
+    /// Run the `true` command.
+    pub fn run(self) -> Result<Option<Output>> {
+        Ok(Some(output(0, Vec::new())))
+    }
+}
+
+/// The `false` command does nothing and fails with exit code 1.
+pub struct False;
+
+impl False {
+    /// Run the `false` command.
+    pub fn run(self) -> Result<Option<Output>> {
+        Ok(Some(output(1, Vec::new())))
+    }
+}
+
+/// The `clear` command clears the terminal.
+///
+/// Instead of depending on the `clear` or `cls` programs, the builtin writes
+/// the escape sequence itself. If stdout is not a terminal, e.g. because the
+/// output of the shell is redirected to a file, it does nothing.
+pub struct Clear;
+
+impl Clear {
+    /// Run the `clear` command.
+    ///
+    /// The sequence is written right away instead of being returned as output.
+    /// Otherwise, it would end up in pipes and files.
+    pub fn run(self) -> Result<Option<Output>> {
+        let mut stdout = std::io::stdout();
+        stdout.write_all(clear_sequence(stdout.is_terminal()))?;
+        stdout.flush()?;
+        Ok(None)
+    }
+}
+
+/// The escape sequence which clears the screen (`ESC [2J`)
+/// and moves the cursor to the top left corner (`ESC [H`).
+///
+/// The line editor uses it for Ctrl-L as well.
+pub fn clear_sequence(is_terminal: bool) -> &'static [u8] {
+    if is_terminal {
+        b"\x1b[2J\x1b[H"
+    } else {
+        b""
+    }
+}
+
+/// The `help` command lists the builtins.
+pub struct Help;
+
+impl Help {
+    /// Run the `help` command.
+    pub fn run(self) -> Result<Option<Output>> {
+        let commands = BUILTINS
+            .iter()
+            .map(|builtin| (builtin.name, builtin.description))
+            .chain(KEYWORDS.iter().copied());
+        let width = commands
+            .clone()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or(0);
+        let mut stdout = Vec::new();
+        writeln!(stdout, "These commands are built into the shell:\n")?;
+        for (name, description) in commands {
+            writeln!(stdout, "  {name:width$}  {description}")?;
+        }
+        Ok(Some(output(0, stdout)))
+    }
+}
+
+/// The `exit` command exits the shell.
+///
+/// The `exit` command exits the shell with the given status code.
+/// If no status code is given, it exits with the status of the last command.
+pub struct Exit {
+    /// The status code to exit with, before it is converted by [`exit_code`].
+    status: i64,
+    /// An argument which isn't a number. Like in bash, the shell still exits, with status 2.
+    invalid_status: Option<String>,
+}
+
+impl Exit {
+    /// Create a new `Exit` command from its arguments.
+    pub fn new(args: &[String], last_status: i32) -> Result<Self> {
+        let (status, invalid_status) = match args {
+            [] => (last_status.into(), None),
+            [status] => match status.parse() {
+                Ok(status) => (status, None),
+                Err(_) => (2, Some(status.clone())),
+            },
+            // Unlike an invalid status, this doesn't exit the shell.
+            _ => return Err("exit: too many arguments".into()),
+        };
+        Ok(Self {
+            status,
+            invalid_status,
+        })
+    }
+
+    /// Run the `exit` command.
+    pub fn run(self, jobs: &mut JobTable) -> Result<Option<Output>> {
+        if let Some(status) = self.invalid_status {
+            eprintln!("Error: exit: {status}: numeric argument required");
+        }
+        jobs.exit();
+        // The `exit` command doesn't produce any output.
+        std::process::exit(exit_code(self.status));
+    }
+}
+
+/// Convert the argument of `exit` to the exit code of the process.
+///
+/// On Unix, only the lowest 8 bits are passed to the parent,
+/// so `exit 256` is 0 and `exit -1` is 255, just like in other shells.
+#[cfg(unix)]
+fn exit_code(status: i64) -> i32 {
+    (status & 0xFF) as i32
+}
+
+/// Convert the argument of `exit` to the exit code of the process.
+///
+/// Windows has 32 bit exit codes, larger values are clamped.
+#[cfg(windows)]
+fn exit_code(status: i64) -> i32 {
+    status.clamp(i32::MIN.into(), i32::MAX.into()) as i32
+}
+
+/// The `logout` command exits a login shell.
+///
+/// It behaves like `exit`, but only in the shell which was started when
+/// the user logged in. In other shells it fails, so that users don't
+/// accidentally close a shell they didn't mean to.
+pub struct Logout {
+    login_shell: bool,
+    /// The status to exit with, like `exit` without an argument.
+    last_status: i32,
+}
+
+impl Logout {
+    /// Create a new `Logout` command.
+    pub fn new(login_shell: bool, last_status: i32) -> Self {
+        Self {
+            login_shell,
+            last_status,
+        }
+    }
+
+    /// Run the `logout` command.
+    pub fn run(self, jobs: &mut JobTable) -> Result<Option<Output>> {
+        if !self.login_shell {
+            return Err("logout: not login shell: use `exit`".into());
+        }
+        Exit::new(&[], self.last_status)?.run(jobs)
+    }
+}
+
+/// The `shopt` command sets (`-s`) and unsets (`-u`) shell options.
+///
+/// The only option so far is `huponexit`.
+pub struct Shopt {
+    args: Vec<String>,
+}
+
+impl Shopt {
+    /// Create a new `Shopt` command.
+    pub fn new(args: Vec<String>) -> Self {
+        Self { args }
+    }
+
+    /// Run the `shopt` command.
+    pub fn run(self, jobs: &mut JobTable) -> Result<Option<Output>> {
+        let Some((flag, names)) = self.args.split_first() else {
+            // Without arguments, list all options.
+            let state = if jobs.huponexit { "on" } else { "off" };
+            return Ok(Some(output(
+                0,
+                format!("huponexit\t{state}\n").into_bytes(),
+            )));
+        };
+        let enable = match flag.as_str() {
+            "-s" => true,
+            "-u" => false,
+            _ => return Err(format!("shopt: {flag}: invalid option").into()),
+        };
+        for name in names {
+            match name.as_str() {
+                "huponexit" => jobs.huponexit = enable,
+                _ => return Err(format!("shopt: {name}: invalid shell option name").into()),
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// The `set` command turns shell options on (`-x`) and off (`+x`).
+///
+/// The options are `-e` (`errexit`), `-x` (`xtrace`) and `-o pipefail`.
+/// Several of them can be combined, like `set -ex` or `set -eo pipefail`.
+/// Options with a letter can also be given by name, like `set -o errexit`.
+/// Without arguments, all options are listed.
+pub struct Set {
+    /// The names of the options to change, with their new state.
+    changes: Vec<(&'static str, bool)>,
+}
+
+/// The options of `set` by name, with their letter if they have one.
+const SET_OPTIONS: &[(&str, Option<char>)] = &[
+    ("errexit", Some('e')),
+    ("xtrace", Some('x')),
+    ("pipefail", None),
+];
+
+impl Set {
+    /// Create a new `Set` command from its arguments.
+    pub fn new(args: &[String]) -> Result<Self> {
+        let mut changes = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let (enable, flags) = match arg.split_at_checked(1) {
+                Some(("-", flags)) if !flags.is_empty() => (true, flags),
+                Some(("+", flags)) if !flags.is_empty() => (false, flags),
+                _ => return Err(format!("set: {arg}: invalid option").into()),
+            };
+            for flag in flags.chars() {
+                let option = if flag == 'o' {
+                    // `-o name` takes the name from the next argument.
+                    let name = args
+                        .next()
+                        .ok_or_else(|| format!("set: {}o: option name required", &arg[..1]))?;
+                    SET_OPTIONS
+                        .iter()
+                        .find(|(option, _)| option == name)
+                        .ok_or_else(|| format!("set: {name}: invalid option name"))?
+                } else {
+                    SET_OPTIONS
+                        .iter()
+                        .find(|(_, letter)| *letter == Some(flag))
+                        .ok_or_else(|| format!("set: {}{flag}: invalid option", &arg[..1]))?
+                };
+                changes.push((option.0, enable));
+            }
+        }
+        Ok(Self { changes })
+    }
+
+    /// Run the `set` command.
+    pub fn run(self, options: &mut Options) -> Result<Option<Output>> {
+        if self.changes.is_empty() {
+            let state = |enabled| if enabled { "on" } else { "off" };
+            let stdout = format!(
+                "errexit\t{}\nxtrace\t{}\npipefail\t{}\n",
+                state(options.errexit),
+                state(options.xtrace),
+                state(options.pipefail)
+            );
+            return Ok(Some(output(0, stdout.into_bytes())));
+        }
+        for (name, enable) in self.changes {
+            match name {
+                "errexit" => options.errexit = enable,
+                "xtrace" => options.xtrace = enable,
+                _ => options.pipefail = enable,
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// The `jobs` command lists the background jobs of the shell.
+pub struct Jobs;
+
+impl Jobs {
+    /// Create a new `Jobs` command.
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Run the `jobs` command.
+    pub fn run(self, jobs: &JobTable) -> Result<Option<Output>> {
+        let mut stdout = Vec::new();
+        for job in jobs.iter() {
+            writeln!(stdout, "[{}]  Running    {}", job.id, job.command)?;
+        }
+        Ok(Some(output(0, stdout)))
+    }
+}
+
+/// The `disown` command removes a job from the job table.
+///
+/// The shell forgets about the job: it isn't listed by `jobs` anymore
+/// and doesn't receive `SIGHUP` when the shell exits.
+/// With `-h`, the job stays in the table and is only protected from `SIGHUP`.
+/// Without a job, like `disown` or `disown -h`, the most recent job is used.
+pub struct Disown {
+    /// `-h`: keep the job, but don't hang it up.
+    nohup: bool,
+    /// The job numbers, written as `%1` or `1`.
+    ids: Vec<usize>,
+}
+
+impl Disown {
+    /// Create a new `Disown` command from its arguments.
+    pub fn new(args: &[String]) -> Result<Self> {
+        let mut disown = Self {
+            nohup: false,
+            ids: Vec::new(),
+        };
+        for arg in args {
+            if arg == "-h" {
+                disown.nohup = true;
+                continue;
+            }
+            let id = arg.strip_prefix('%').unwrap_or(arg);
+            let id = id
+                .parse()
+                .map_err(|_| format!("disown: {arg}: no such job"))?;
+            disown.ids.push(id);
+        }
+        Ok(disown)
+    }
+
+    /// Run the `disown` command.
+    pub fn run(self, jobs: &mut JobTable) -> Result<Option<Output>> {
+        let ids: Vec<Option<usize>> = if self.ids.is_empty() {
+            vec![None]
+        } else {
+            self.ids.into_iter().map(Some).collect()
+        };
+        for id in ids {
+            let job = jobs.get_mut(id).ok_or_else(|| match id {
+                Some(id) => format!("disown: %{id}: no such job"),
+                None => "disown: current: no such job".to_string(),
+            })?;
+            if self.nohup {
+                job.nohup = true;
+            } else {
+                let id = job.id;
+                jobs.remove(id);
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// The `wait` command waits until background jobs are done.
+///
+/// Jobs are given by their number like `%1`, or by their process ID.
+/// Without arguments, it waits for all jobs. The exit code is the one of the last job.
+pub struct Wait {
+    jobs: Vec<WaitFor>,
+}
+
+/// A job to wait for.
+#[derive(Debug, PartialEq)]
+enum WaitFor {
+    /// `%1`
+    Job(usize),
+    /// The process ID of the job.
+    Pid(u32),
+}
+
+impl Wait {
+    /// Create a new `Wait` command from its arguments.
+    pub fn new(args: &[String]) -> Result<Self> {
+        let jobs = args
+            .iter()
+            .map(|arg| {
+                let job = match arg.strip_prefix('%') {
+                    Some(id) => id.parse().map(WaitFor::Job),
+                    None => arg.parse().map(WaitFor::Pid),
+                };
+                job.map_err(|_| format!("wait: `{arg}': not a pid or valid job spec").into())
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { jobs })
+    }
+
+    /// Run the `wait` command.
+    pub fn run(self, jobs: &mut JobTable) -> Result<Option<Output>> {
+        if self.jobs.is_empty() {
+            let ids: Vec<usize> = jobs.iter().map(|job| job.id).collect();
+            for id in ids {
+                jobs.wait(id);
+            }
+            return Ok(None);
+        }
+
+        let mut code = 0;
+        let mut stderr = Vec::new();
+        for job in self.jobs {
+            let id = match job {
+                WaitFor::Job(id) => Some(id),
+                WaitFor::Pid(pid) => jobs.find_pid(pid),
+            };
+            code = match id.and_then(|id| jobs.wait(id)) {
+                // Commands killed by a signal don't have an exit code.
+                Some(status) => status?.code().unwrap_or(1),
+                // Like in bash, unknown jobs are an error, but the other jobs are still waited for.
+                None => {
+                    match job {
+                        WaitFor::Job(id) => writeln!(stderr, "wait: %{id}: no such job")?,
+                        WaitFor::Pid(pid) => {
+                            writeln!(stderr, "wait: pid {pid} is not a child of this shell")?
+                        }
+                    }
+                    127
+                }
+            };
+        }
+        let mut output = output(code, Vec::new());
+        output.stderr = stderr;
+        Ok(Some(output))
+    }
+}
+
+/// The `exec` command replaces the shell with another program.
+///
+/// Without a program, the redirections are applied to the shell itself.
+/// For example, after `exec > log` all further output of the shell goes to `log`.
+pub struct Exec {
+    /// The program to run and its arguments.
+    args: Vec<String>,
+    redirects: Vec<Redirect>,
+}
+
+impl Exec {
+    /// Create a new `Exec` command.
+    pub fn new(args: Vec<String>, redirects: Vec<Redirect>) -> Self {
+        Self { args, redirects }
+    }
+
+    /// Run the `exec` command.
+    #[cfg(unix)]
+    pub fn run(self) -> Result<Option<Output>> {
+        use std::os::{fd::AsRawFd, unix::process::CommandExt};
+
+        // Make sure everything written so far ends up in the old files.
+        std::io::stdout().flush()?;
+        std::io::stderr().flush()?;
+
+        for redirect in &self.redirects {
+            // `dup2` points the file descriptor of the shell to the file.
+            // The file itself can be closed afterwards.
+            let result = match redirect.kind.duplicated_fd() {
+                // `exec 2>&1` copies one file descriptor of the shell to the other.
+                Some(fd) => unsafe { libc::dup2(fd, redirect.kind.fd()) },
+                None => unsafe { libc::dup2(redirect.open()?.as_raw_fd(), redirect.kind.fd()) },
+            };
+            if result == -1 {
+                return Err(std::io::Error::last_os_error().into());
+            }
+        }
+
+        match self.args.split_first() {
+            // `exec` only returns if the program couldn't be started.
+            Some((binary, args)) => {
+                Err(std::process::Command::new(binary).args(args).exec().into())
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Run the `exec` command.
+    #[cfg(not(unix))]
+    pub fn run(self) -> Result<Option<Output>> {
+        Err("exec: not supported on this platform".into())
+    }
+}
+
+/// The `source` command, also known as `.`, runs the commands of a file.
+///
+/// Unlike running the file as a script, the commands run in the shell itself.
+/// This way, `cd`, aliases and variables of the file stay in effect afterwards.
+pub struct Source {
+    path: PathBuf,
+}
+
+impl Source {
+    /// Create a new `Source` command from its arguments.
+    pub fn new(args: &[String]) -> Result<Self> {
+        let path = args.first().ok_or("source: filename argument required")?;
+        Ok(Self {
+            path: PathBuf::from(path),
+        })
+    }
+
+    /// Run the `source` command, passing each line of the file to `run_line`.
+    ///
+    /// The output of the commands is printed by `run_line` as they run.
+    /// The status is the one of the last command in the file.
+    pub fn run(
+        self,
+        mut run_line: impl FnMut(&str) -> Option<ExitStatus>,
+    ) -> Result<Option<Output>> {
+        let script = std::fs::read_to_string(&self.path)
+            .map_err(|e| format!("source: {}: {e}", self.path.display()))?;
+        let mut status = ExitStatus::from_raw(0);
+        let mut lines = script.lines().map(String::from);
+        while let Some(line) = lines.next() {
+            let line = read_continued_lines(&line, || lines.next());
+            let line = read_here_documents(&line, || lines.next());
+            if let Some(line_status) = run_line(&line) {
+                status = line_status;
+            }
+        }
+        Ok(Some(Output {
+            status,
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        }))
+    }
+}
+
+/// The `repeat` command runs a command several times, like `repeat 3 echo hi`.
+pub struct Repeat {
+    count: usize,
+    /// The command and its arguments.
+    command: Vec<String>,
+}
+
+impl Repeat {
+    /// Create a new `Repeat` command from its arguments.
+    pub fn new(args: &[String]) -> Result<Self> {
+        let [count, command @ ..] = args else {
+            return Err("repeat: usage: repeat N command".into());
+        };
+        if command.is_empty() {
+            return Err("repeat: usage: repeat N command".into());
+        }
+        let count = count
+            .parse()
+            .map_err(|_| format!("repeat: {count}: numeric argument required"))?;
+        Ok(Self {
+            count,
+            command: command.to_vec(),
+        })
+    }
+
+    /// Run the `repeat` command, passing the command to `run_command` each time.
+    ///
+    /// The output of all runs is collected. The status is the one of the last run.
+    pub fn run(self, mut run_command: impl FnMut(&[String]) -> Output) -> Result<Option<Output>> {
+        let mut repeated = output(0, Vec::new());
+        for _ in 0..self.count {
+            let output = run_command(&self.command);
+            repeated.stdout.extend(output.stdout);
+            repeated.status = output.status;
+        }
+        Ok(Some(repeated))
+    }
+}
+
+#[cfg(unix)]
+use std::os::unix::process::ExitStatusExt;
+
+#[cfg(windows)]
+use std::os::windows::process::ExitStatusExt;
+
+/// Create the `Output` of a builtin which exits with `code`.
+pub fn output(code: i32, stdout: Vec<u8>) -> Output {
+    // On Unix, the raw status is the one returned by `waitpid`,
+    // which stores the exit code in the second byte.
+    #[cfg(unix)]
+    let status = std::process::ExitStatus::from_raw(code << 8);
+    #[cfg(windows)]
+    let status = std::process::ExitStatus::from_raw(code as u32);
+
+    Output {
+        status,
+        stdout,
+        stderr: Vec::new(),
+    }
+}
+
+// Store history file in current path. This is convenient for debugging purposes.
+// In a real shell, the history would be stored in a file in the user's home directory.
+const DEFAULT_HISTORY_PATH: &str = ".history";
+
+/// How many commands are kept in the history, unless `HISTSIZE` says otherwise.
+const DEFAULT_HISTORY_SIZE: usize = 1000;
+
+/// The `history` command displays the command history.
+pub struct History {
+    history_path: PathBuf,
+    /// The maximum number of commands in the history file. The oldest ones are removed first.
+    size: usize,
+    /// The number of commands in the history file, counted on the first `add`.
+    /// Later commands only update it, so the file isn't read again for every command.
+    len: Cell<Option<usize>>,
+}
+
+impl History {
+    /// Create a new `History` command.
+    pub fn new() -> Self {
+        // The path can be overridden by setting the `HISTORY_PATH` environment variable.
+        let history_path = std::env::var("HISTORY_PATH")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from(DEFAULT_HISTORY_PATH));
+        // Like in Bash, `HISTSIZE=0` turns the history off.
+        let size = std::env::var("HISTSIZE")
+            .ok()
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(DEFAULT_HISTORY_SIZE);
+
+        Self {
+            history_path,
+            size,
+            len: Cell::new(None),
+        }
+    }
+
+    /// Add a command to the history.
+    ///
+    /// Blank lines are not added. Like `HISTCONTROL=ignoredups` in Bash,
+    /// a command is not added again if it is the same as the previous one.
+    /// If the history gets longer than its size, the oldest commands are removed.
+    pub fn add(&self, command: &str) -> Result<()> {
+        if self.size == 0 || command.trim().is_empty() || self.ends_with(command)? {
+            return Ok(());
+        }
+        let mut history = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.history_path)?;
+        writeln!(history, "{command}")?;
+        let len = match self.len.get() {
+            Some(len) => len + 1,
+            None => self.entries()?.len(),
+        };
+        self.len.set(Some(len));
+        if len > self.size {
+            self.truncate()?;
+        }
+        Ok(())
+    }
+
+    /// Remove the oldest commands, so that at most `size` commands are left.
+    fn truncate(&self) -> Result<()> {
+        let entries = self.entries()?;
+        let kept = &entries[entries.len().saturating_sub(self.size)..];
+        let mut history = kept.join("\n");
+        history.push('\n');
+        std::fs::write(&self.history_path, history)?;
+        self.len.set(Some(kept.len()));
+        Ok(())
+    }
+
+    /// Whether `command` is the last command in the history.
+    ///
+    /// Only the end of the file is read, the history can get long.
+    fn ends_with(&self, command: &str) -> Result<bool> {
+        let mut history = match std::fs::File::open(&self.history_path) {
+            Ok(history) => history,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e.into()),
+        };
+        let entry = format!("\n{command}\n");
+        let start = history.metadata()?.len().saturating_sub(entry.len() as u64);
+        history.seek(SeekFrom::Start(start))?;
+        let mut tail = Vec::new();
+        history.read_to_end(&mut tail)?;
+        // The first entry has no newline before it.
+        Ok(tail == entry.as_bytes() || (start == 0 && tail == entry.as_bytes()[1..]))
+    }
+
+    /// Replace `!!` in `line` with the previous command.
+    /// `!N` is replaced with the command number `N`, as shown by `history`,
+    /// and `!-N` with the `N`th last command.
+    ///
+    /// Add the expanded line to the history, not the original one.
+    /// Otherwise, running `!!` twice would expand to `!!` itself.
+    ///
+    /// Like in Bash, there is no expansion inside single quotes.
+    /// `\!` is a literal `!`, the backslash is removed.
+    pub fn expand(&self, line: &str) -> Result<Expansion> {
+        let mut expanded = String::with_capacity(line.len());
+        let mut substituted = false;
+        let mut single_quoted = false;
+        let mut double_quoted = false;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\'' if !double_quoted => single_quoted = !single_quoted,
+                '"' if !single_quoted => double_quoted = !double_quoted,
+                '\\' if !single_quoted => {
+                    match chars.next_if_eq(&'!') {
+                        Some(bang) => expanded.push(bang),
+                        // Keep other escapes for the parser, e.g. `\'` must not start a quote.
+                        None => {
+                            expanded.push(c);
+                            expanded.extend(chars.next());
+                        }
+                    }
+                    continue;
+                }
+                '!' if !single_quoted && chars.next_if_eq(&'!').is_some() => {
+                    let last = self.last()?.ok_or("!!: event not found")?;
+                    expanded.push_str(&last);
+                    substituted = true;
+                    continue;
+                }
+                '!' if !single_quoted && starts_with_event_number(chars.clone()) => {
+                    let event: String = chars
+                        .next_if_eq(&'-')
+                        .into_iter()
+                        .chain(std::iter::from_fn(|| chars.next_if(char::is_ascii_digit)))
+                        .collect();
+                    expanded.push_str(&self.event(&event)?);
+                    substituted = true;
+                    continue;
+                }
+                _ => {}
+            }
+            expanded.push(c);
+        }
+        Ok(Expansion {
+            line: expanded,
+            substituted,
+        })
+    }
+
+    /// Get the command for `!N` or `!-N`, where `event` is `N` or `-N`.
+    fn event(&self, event: &str) -> Result<String> {
+        let entries = self.entries()?;
+        let index = match event.strip_prefix('-') {
+            Some(number) => number
+                .parse()
+                .ok()
+                .and_then(|number| entries.len().checked_sub(number)),
+            None => event
+                .parse::<usize>()
+                .ok()
+                .and_then(|number| number.checked_sub(1)),
+        };
+        index
+            .and_then(|index| entries.get(index))
+            .cloned()
+            .ok_or_else(|| format!("!{event}: event not found").into())
+    }
+
+    /// Get the most recent command in the history, if any.
+    fn last(&self) -> Result<Option<String>> {
+        Ok(self.entries()?.pop())
+    }
+
+    /// Get all commands in the history, oldest first.
+    pub fn entries(&self) -> Result<Vec<String>> {
+        match std::fs::read_to_string(&self.history_path) {
+            Ok(history) => Ok(history.lines().map(String::from).collect()),
+            // No history file means no commands were run yet.
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Remove all commands from the history, for `history -c`.
+    pub fn clear(self) -> Result<Option<Output>> {
+        match std::fs::remove_file(&self.history_path) {
+            // Without a history file, there is nothing to clear.
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(None),
+        }
+    }
+
+    /// Show the commands in the history, numbered from 1.
+    ///
+    /// With a `count`, like `history 10`, only the last `count` commands are shown.
+    pub fn run(self, count: Option<usize>) -> Result<Option<Output>> {
+        let entries = self.entries()?;
+        let skip = count.map_or(0, |count| entries.len().saturating_sub(count));
+        let mut stdout = Vec::new();
+        for (number, entry) in entries.iter().enumerate().skip(skip) {
+            writeln!(stdout, "{:>5}  {entry}", number + 1)?;
+        }
+        Ok(Some(output(0, stdout)))
+    }
+
+    /// Show the commands which contain `term`, for `history --search term`.
+    ///
+    /// They keep their numbers, so they can be run again with `!N`.
+    pub fn search(self, term: &str) -> Result<Option<Output>> {
+        let mut stdout = Vec::new();
+        for (number, entry) in self.entries()?.iter().enumerate() {
+            if entry.contains(term) {
+                writeln!(stdout, "{:>5}  {entry}", number + 1)?;
+            }
+        }
+        Ok(Some(output(0, stdout)))
+    }
+
+    /// Show the history as a JSON array for other programs, for `history --json`.
+    ///
+    /// Each command is an object like `{"index": 1, "command": "ls"}`,
+    /// with the same numbers as in `history`.
+    pub fn json(self) -> Result<Option<Output>> {
+        let objects: Vec<String> = self
+            .entries()?
+            .iter()
+            .enumerate()
+            .map(|(number, entry)| {
+                format!(
+                    "{{\"index\": {}, \"command\": {}}}",
+                    number + 1,
+                    json_string(entry)
+                )
+            })
+            .collect();
+        let stdout = format!("[{}]\n", objects.join(", "));
+        Ok(Some(output(0, stdout.into_bytes())))
+    }
+}
+
+/// `text` as a JSON string, in quotes and with quotes, backslashes
+/// and control characters escaped.
+fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                json.push_str(&format!("\\u{:04x}", c as u32));
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// A line after history expansion, see [`History::expand`].
+pub struct Expansion {
+    pub line: String,
+    /// Whether a command from the history was inserted, e.g. for `!!`.
+    /// Removing the backslash of `\!` doesn't count.
+    pub substituted: bool,
+}
+
+/// Whether `chars` start with `N` or `-N`, the number of `!N` or `!-N`.
+fn starts_with_event_number(mut chars: impl Iterator<Item = char>) -> bool {
+    let first = chars.next();
+    let digit = if first == Some('-') {
+        chars.next()
+    } else {
+        first
+    };
+    digit.is_some_and(|c| c.is_ascii_digit())
+}
+
+/// Maps alias names to the text they expand to.
+pub type Aliases = HashMap<String, String>;
+
+/// The `alias` command defines or displays aliases.
+///
+/// `alias name=value` defines an alias, `alias name` displays it
+/// and `alias` without arguments displays all aliases.
+/// The parser replaces aliased command names with their value.
+pub struct Alias {
+    args: Vec<String>,
+}
+
+impl Alias {
+    /// Create a new `Alias` command.
+    pub fn new(args: Vec<String>) -> Self {
+        Self { args }
+    }
+
+    /// Run the `alias` command.
+    pub fn run(self, aliases: &mut Aliases) -> Result<Option<Output>> {
+        let mut stdout = Vec::new();
+        if self.args.is_empty() {
+            let mut names: Vec<_> = aliases.keys().collect();
+            names.sort();
+            for name in names {
+                writeln!(stdout, "alias {name}='{}'", aliases[name])?;
+            }
+        }
+
+        for arg in self.args {
+            match arg.split_once('=') {
+                Some((name, value)) => {
+                    aliases.insert(name.to_string(), value.to_string());
+                }
+                None => {
+                    let value = aliases
+                        .get(&arg)
+                        .ok_or_else(|| format!("alias: {arg}: not found"))?;
+                    writeln!(stdout, "alias {arg}='{value}'")?;
+                }
+            }
+        }
+
+        Ok(Some(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout,
+            stderr: Vec::new(),
+        }))
+    }
+}
+
+/// The `complete` command registers the words which Tab completes the first argument of
+/// a command to, e.g. `complete -c cargo build test` for `cargo b<Tab>`.
+///
+/// Without arguments, it shows the registered completions.
+pub struct Complete {
+    /// The command and its words.
+    spec: Option<(String, Vec<String>)>,
+}
+
+impl Complete {
+    /// Create a new `Complete` command from its arguments.
+    pub fn new(args: &[String]) -> Result<Self> {
+        match args {
+            [] => Ok(Self { spec: None }),
+            [flag, command, words @ ..] if flag == "-c" => Ok(Self {
+                spec: Some((command.clone(), words.to_vec())),
+            }),
+            _ => Err("complete: usage: complete [-c command word...]".into()),
+        }
+    }
+
+    /// Run the `complete` command.
+    pub fn run(self, completions: &mut Completions) -> Result<Option<Output>> {
+        if let Some((command, words)) = self.spec {
+            completions.insert(command, words);
+            return Ok(None);
+        }
+        let mut commands: Vec<_> = completions.keys().collect();
+        commands.sort();
+        let mut stdout = Vec::new();
+        for command in commands {
+            writeln!(
+                stdout,
+                "complete -c {command} {}",
+                completions[command].join(" ")
+            )?;
+        }
+        Ok(Some(output(0, stdout)))
+    }
+}
+
+/// The `sort` command sorts lines of text.
+///
+/// It reads the given files, or stdin if there are none.
+/// Lines are compared lexicographically, unless `-n` is given.
+/// A builtin `sort` makes the shell independent of the system's `sort`,
+/// which behaves differently depending on the platform and the locale.
+pub struct Sort {
+    /// `-r`: reverse the result.
+    reverse: bool,
+    /// `-n`: compare lines by their leading number.
+    numeric: bool,
+    /// `-u`: only output the first of lines which compare equal.
+    unique: bool,
+    files: Vec<PathBuf>,
+}
+
+impl Sort {
+    /// Create a new `Sort` command from its arguments.
+    pub fn new(args: &[String]) -> Result<Self> {
+        let mut sort = Self {
+            reverse: false,
+            numeric: false,
+            unique: false,
+            files: Vec::new(),
+        };
+        for arg in args {
+            match arg.strip_prefix('-') {
+                Some(flags) if !flags.is_empty() => {
+                    for flag in flags.chars() {
+                        match flag {
+                            'r' => sort.reverse = true,
+                            'n' => sort.numeric = true,
+                            'u' => sort.unique = true,
+                            _ => return Err(format!("sort: invalid option -- '{flag}'").into()),
+                        }
+                    }
+                }
+                _ => sort.files.push(PathBuf::from(arg)),
+            }
+        }
+        Ok(sort)
+    }
+
+    /// Run the `sort` command.
+    pub fn run(self, mut stdin: impl Read) -> Result<Option<Output>> {
+        let mut input = String::new();
+        if self.files.is_empty() {
+            stdin.read_to_string(&mut input)?;
+        }
+        for file in &self.files {
+            input.push_str(&std::fs::read_to_string(file)?);
+            if !input.ends_with('\n') {
+                input.push('\n');
+            }
+        }
+
+        let mut lines: Vec<&str> = input.lines().collect();
+        lines.sort_by(|a, b| self.compare(a, b).then_with(|| a.cmp(b)));
+        if self.reverse {
+            lines.reverse();
+        }
+        if self.unique {
+            lines.dedup_by(|a, b| self.compare(a, b) == Ordering::Equal);
+        }
+
+        let mut stdout = Vec::new();
+        for line in lines {
+            writeln!(stdout, "{line}")?;
+        }
+        Ok(Some(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout,
+            stderr: Vec::new(),
+        }))
+    }
+
+    /// Compare two lines by the sort key.
+    fn compare(&self, a: &str, b: &str) -> Ordering {
+        if self.numeric {
+            leading_number(a).total_cmp(&leading_number(b))
+        } else {
+            a.cmp(b)
+        }
+    }
+}
+
+/// Parse the number at the start of `line`. Lines without a number count as zero.
+fn leading_number(line: &str) -> f64 {
+    let line = line.trim_start();
+    let end = line
+        .char_indices()
+        .find(|&(i, c)| !(c.is_ascii_digit() || c == '.' || (i == 0 && c == '-')))
+        .map_or(line.len(), |(i, _)| i);
+    line[..end].parse().unwrap_or(0.0)
+}
+
+/// How many lines `head` and `tail` print by default.
+const DEFAULT_LINE_COUNT: usize = 10;
+
+/// Parse the arguments of `head` and `tail`: `-n N` and a list of files.
+fn parse_line_count(name: &str, args: &[String]) -> Result<(usize, Vec<PathBuf>)> {
+    let mut count = DEFAULT_LINE_COUNT;
+    let mut files = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = match arg.strip_prefix("-n") {
+            // `-n 5`
+            Some("") => args
+                .next()
+                .ok_or_else(|| format!("{name}: option requires an argument -- 'n'"))?,
+            // `-n5`
+            Some(value) => value,
+            // Only `-n` is supported, other options like `-c` aren't file names either.
+            None if arg.starts_with('-') && arg.len() > 1 => {
+                let option = arg[1..].chars().next().unwrap_or('-');
+                return Err(format!("{name}: invalid option -- '{option}'").into());
+            }
+            None => {
+                files.push(PathBuf::from(arg));
+                continue;
+            }
+        };
+        count = value
+            .parse()
+            .map_err(|_| format!("{name}: invalid number of lines: '{value}'"))?;
+    }
+    Ok((count, files))
+}
+
+/// Open the files to read, or use stdin if there are none.
+fn open_inputs<'a>(files: &[PathBuf], stdin: impl Read + 'a) -> Result<Vec<Box<dyn Read + 'a>>> {
+    if files.is_empty() {
+        return Ok(vec![Box::new(stdin)]);
+    }
+    files
+        .iter()
+        .map(|file| Ok(Box::new(std::fs::File::open(file)?) as Box<dyn Read>))
+        .collect()
+}
+
+/// The `head` command prints the first lines of its input.
+pub struct Head {
+    /// `-n`: how many lines to print.
+    count: usize,
+    files: Vec<PathBuf>,
+}
+
+impl Head {
+    /// Create a new `Head` command from its arguments.
+    pub fn new(args: &[String]) -> Result<Self> {
+        let (count, files) = parse_line_count("head", args)?;
+        Ok(Self { count, files })
+    }
+
+    /// Run the `head` command.
+    ///
+    /// Reading stops after `count` lines and the input is closed.
+    /// This way, a command writing into `head`, like `yes | head`,
+    /// receives `SIGPIPE` instead of running forever.
+    pub fn run(self, stdin: impl Read) -> Result<Option<Output>> {
+        let mut stdout = Vec::new();
+        let mut remaining = self.count;
+        for input in open_inputs(&self.files, stdin)? {
+            let mut input = BufReader::new(input);
+            while remaining > 0 {
+                let mut line = Vec::new();
+                if input.read_until(b'\n', &mut line)? == 0 {
+                    break;
+                }
+                stdout.extend(line);
+                remaining -= 1;
+            }
+        }
+        Ok(Some(output(0, stdout)))
+    }
+}
+
+/// The `tail` command prints the last lines of its input.
+pub struct Tail {
+    /// `-n`: how many lines to print.
+    count: usize,
+    files: Vec<PathBuf>,
+}
+
+impl Tail {
+    /// Create a new `Tail` command from its arguments.
+    pub fn new(args: &[String]) -> Result<Self> {
+        let (count, files) = parse_line_count("tail", args)?;
+        Ok(Self { count, files })
+    }
+
+    /// Run the `tail` command.
+    pub fn run(self, stdin: impl Read) -> Result<Option<Output>> {
+        // Only keep the last `count` lines in memory.
+        let mut lines = VecDeque::with_capacity(self.count);
+        for input in open_inputs(&self.files, stdin)? {
+            for line in BufReader::new(input).split(b'\n') {
+                if lines.len() == self.count {
+                    lines.pop_front();
+                }
+                if self.count > 0 {
+                    lines.push_back(line?);
+                }
+            }
+        }
+
+        let mut stdout = Vec::new();
+        for line in lines {
+            stdout.extend(line);
+            stdout.push(b'\n');
+        }
+        Ok(Some(output(0, stdout)))
+    }
+}
+
+/// The `tr` command translates or deletes characters.
+///
+/// `tr SET1 SET2` replaces each character of `SET1` with the character at
+/// the same position in `SET2`. If `SET2` is shorter, its last character is repeated.
+/// `tr -d SET1` deletes the characters of `SET1`.
+/// Sets may contain ranges like `a-z`.
+pub struct Tr {
+    from: Vec<char>,
+    /// `None` for `-d`.
+    to: Option<Vec<char>>,
+}
+
+impl Tr {
+    /// Create a new `Tr` command from its arguments.
+    pub fn new(args: &[String]) -> Result<Self> {
+        match args {
+            [flag, set] if flag == "-d" => Ok(Self {
+                from: expand_set(set),
+                to: None,
+            }),
+            [from, to] => {
+                let to = expand_set(to);
+                if to.is_empty() && !from.is_empty() {
+                    return Err("tr: when not truncating set1, string2 must be non-empty".into());
+                }
+                Ok(Self {
+                    from: expand_set(from),
+                    to: Some(to),
+                })
+            }
+            _ => Err("tr: expected two sets or -d and one set".into()),
+        }
+    }
+
+    /// Run the `tr` command.
+    pub fn run(self, stdin: impl Read) -> Result<Option<Output>> {
+        let mut stdout = Vec::new();
+        self.translate(stdin, &mut stdout)?;
+        Ok(Some(output(0, stdout)))
+    }
+
+    /// Translate `stdin` into `stdout` chunk by chunk, as the input arrives.
+    /// This way, `tr` works on input which never ends, like the output of `yes`.
+    ///
+    /// Bytes which are not valid UTF-8 are kept as they are.
+    pub fn translate(&self, mut stdin: impl Read, mut stdout: impl Write) -> Result<()> {
+        let mut buffer = [0; 8192];
+        // The first bytes of a character which is split between two chunks.
+        let mut pending = 0;
+        loop {
+            let read = match stdin.read(&mut buffer[pending..]) {
+                Ok(read) => read,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            let end = pending + read;
+            let mut translated = Vec::with_capacity(end);
+            let mut offset = 0;
+            pending = 0;
+            for chunk in buffer[..end].utf8_chunks() {
+                let text: String = chunk.valid().chars().filter_map(|c| self.map(c)).collect();
+                translated.extend_from_slice(text.as_bytes());
+                offset += chunk.valid().len() + chunk.invalid().len();
+                let incomplete =
+                    std::str::from_utf8(chunk.invalid()).is_err_and(|e| e.error_len().is_none());
+                // At the end of the input, an incomplete character is kept as it is.
+                if incomplete && offset == end && read > 0 {
+                    pending = chunk.invalid().len();
+                } else {
+                    translated.extend_from_slice(chunk.invalid());
+                }
+            }
+            stdout.write_all(&translated)?;
+            if read == 0 {
+                return Ok(());
+            }
+            buffer.copy_within(end - pending..end, 0);
+        }
+    }
+
+    /// The translation of `c`, or `None` if it is deleted.
+    fn map(&self, c: char) -> Option<char> {
+        let Some(i) = self.from.iter().position(|&from| from == c) else {
+            return Some(c);
+        };
+        // Deleted characters are dropped.
+        let to = self.to.as_ref()?;
+        Some(*to.get(i).or(to.last()).unwrap_or(&c))
+    }
+}
+
+/// The `read` command reads a line from stdin into variables.
+/// It is not called `Read` to avoid confusion with [`std::io::Read`].
+///
+/// The line is split into words. Each name gets one word,
+/// the last name gets the rest of the line. Without names, the line is stored in `REPLY`.
+/// The variables are not exported, unless they already were.
+pub struct ReadLine {
+    names: Vec<String>,
+}
+
+impl ReadLine {
+    /// Create a new `ReadLine` command from its arguments.
+    pub fn new(args: &[String]) -> Result<Self> {
+        if let Some(name) = args.iter().find(|name| !is_name(name)) {
+            return Err(format!("read: `{name}': not a valid identifier").into());
+        }
+        let names = match args {
+            [] => vec!["REPLY".to_string()],
+            names => names.to_vec(),
+        };
+        Ok(Self { names })
+    }
+
+    /// Run the `read` command.
+    ///
+    /// Only a single line is consumed, so the shell can read the next command
+    /// from the same input. The exit code is 1 at the end of the input.
+    pub fn run(self, mut stdin: impl BufRead, variables: &mut Variables) -> Result<Option<Output>> {
+        let mut line = String::new();
+        stdin.read_line(&mut line)?;
+        let code = if line.ends_with('\n') { 0 } else { 1 };
+
+        let mut rest = line.trim();
+        for (i, name) in self.names.iter().enumerate() {
+            let value = if i + 1 == self.names.len() {
+                rest
+            } else {
+                let (word, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                rest = tail.trim_start();
+                word
+            };
+            variables.set(name, value);
+        }
+        Ok(Some(output(code, Vec::new())))
+    }
+}
+
+/// Expand the ranges in a set of `tr`, e.g. `a-d` becomes `abcd`.
+/// A `-` at the start or the end of the set is taken literally.
+fn expand_set(set: &str) -> Vec<char> {
+    let chars: Vec<char> = set.chars().collect();
+    let mut expanded = Vec::with_capacity(chars.len());
+    let mut i = 0;
+    while i < chars.len() {
+        match chars.get(i..i + 3) {
+            Some(&[start, '-', end]) if start <= end => {
+                expanded.extend(start..=end);
+                i += 3;
+            }
+            _ => {
+                expanded.push(chars[i]);
+                i += 1;
+            }
+        }
+    }
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A history without a file, i.e. without any entries.
+    fn empty_history() -> History {
+        History {
+            history_path: PathBuf::from("does/not/exist"),
+            size: DEFAULT_HISTORY_SIZE,
+            len: Cell::new(None),
+        }
+    }
+
+    #[test]
+    fn json_strings_are_escaped() {
+        assert_eq!(json_string("ls"), r#""ls""#);
+        assert_eq!(json_string(r#"echo "a\b""#), r#""echo \"a\\b\"""#);
+        assert_eq!(json_string("a\tb\u{1}"), r#""a\tb\u0001""#);
+    }
+
+    #[test]
+    fn escaped_bang_is_literal() {
+        let history = empty_history();
+        assert_eq!(history.expand(r"echo \!foo").unwrap().line, "echo !foo");
+        assert_eq!(history.expand(r"echo \!!").unwrap().line, "echo !!");
+        assert!(!history.expand(r"echo \!foo").unwrap().substituted);
+    }
+
+    #[test]
+    fn other_escapes_are_kept() {
+        let history = empty_history();
+        let line = r#"echo '\!' "a\b" \' \\"#;
+        assert_eq!(history.expand(line).unwrap().line, line);
+    }
+
+    #[test]
+    fn bang_bang_in_single_quotes_is_not_expanded() {
+        let history = empty_history();
+        assert_eq!(history.expand("echo '!!'").unwrap().line, "echo '!!'");
+        assert!(history.expand("echo \"!!\"").is_err());
+    }
+
+    #[test]
+    fn bang_number_is_expanded() {
+        let history_path =
+            std::env::temp_dir().join(format!("rush-history-{}", std::process::id()));
+        std::fs::write(&history_path, "echo one\necho two\necho three\n").unwrap();
+        let history = History {
+            history_path: history_path.clone(),
+            size: DEFAULT_HISTORY_SIZE,
+            len: Cell::new(None),
+        };
+        assert_eq!(history.expand("!1").unwrap().line, "echo one");
+        assert!(history.expand("!1").unwrap().substituted);
+        assert_eq!(
+            history.expand("!-1 | !2").unwrap().line,
+            "echo three | echo two"
+        );
+        assert_eq!(history.expand("!-3").unwrap().line, "echo one");
+        assert!(history.expand("!4").is_err());
+        assert!(history.expand("!0").is_err());
+        assert!(history.expand("!-4").is_err());
+        // Without a number, `!` is a normal character.
+        assert_eq!(history.expand("echo ! !- !x").unwrap().line, "echo ! !- !x");
+        std::fs::remove_file(history_path).unwrap();
+    }
+
+    #[test]
+    fn history_keeps_the_most_recent_commands() {
+        let history_path =
+            std::env::temp_dir().join(format!("rush-history-size-{}", std::process::id()));
+        let history = History {
+            history_path: history_path.clone(),
+            size: 2,
+            len: Cell::new(None),
+        };
+        for command in ["echo one", "echo two", "echo three"] {
+            history.add(command).unwrap();
+        }
+        assert_eq!(history.entries().unwrap(), ["echo two", "echo three"]);
+        assert_eq!(history.len.get(), Some(2));
+        std::fs::remove_file(history_path).unwrap();
+
+        let disabled = History {
+            history_path: PathBuf::from("does/not/exist"),
+            size: 0,
+            len: Cell::new(None),
+        };
+        disabled.add("echo one").unwrap();
+        assert!(disabled.entries().unwrap().is_empty());
+    }
+
+    fn sort(args: &[&str], input: &str) -> String {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let output = Sort::new(&args).unwrap().run(input.as_bytes()).unwrap();
+        String::from_utf8(output.unwrap().stdout).unwrap()
+    }
+
+    #[test]
+    fn sort_is_lexicographic_by_default() {
+        assert_eq!(sort(&[], "b\n10\na\n9\n"), "10\n9\na\nb\n");
+    }
+
+    #[test]
+    fn sort_reverse_numeric() {
+        assert_eq!(sort(&["-rn"], "9\n10\n-1\n10\nx\n"), "10\n10\n9\nx\n-1\n");
+        assert_eq!(sort(&["-r", "-n"], "1\n2\n"), "2\n1\n");
+    }
+
+    #[test]
+    fn sort_unique() {
+        assert_eq!(sort(&["-u"], "b\na\nb\na\n"), "a\nb\n");
+    }
+
+    #[test]
+    fn sort_unique_compares_by_number() {
+        assert_eq!(sort(&["-nu"], "2\n1\n01\n"), "01\n2\n");
+        assert_eq!(sort(&["-rnu"], "1\n3\n2\n3\n"), "3\n2\n1\n");
+    }
+
+    fn head(args: &[&str], input: &str) -> String {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let output = Head::new(&args).unwrap().run(input.as_bytes()).unwrap();
+        String::from_utf8(output.unwrap().stdout).unwrap()
+    }
+
+    fn tail(args: &[&str], input: &str) -> String {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let output = Tail::new(&args).unwrap().run(input.as_bytes()).unwrap();
+        String::from_utf8(output.unwrap().stdout).unwrap()
+    }
+
+    #[test]
+    fn head_prints_first_lines() {
+        let input: String = (1..=20).map(|i| format!("{i}\n")).collect();
+        assert_eq!(head(&["-n", "3"], &input), "1\n2\n3\n");
+        assert_eq!(head(&["-n2"], &input), "1\n2\n");
+        assert_eq!(head(&[], &input).lines().count(), DEFAULT_LINE_COUNT);
+        assert_eq!(head(&["-n", "5"], "a\nb"), "a\nb");
+    }
+
+    #[test]
+    fn head_stops_reading_after_count_lines() {
+        // The input never ends, so this only returns if `head` stops reading.
+        let output = Head::new(&["-n".to_string(), "3".to_string()])
+            .unwrap()
+            .run(std::io::repeat(b'\n'))
+            .unwrap();
+        assert_eq!(output.unwrap().stdout, b"\n\n\n");
+    }
+
+    #[test]
+    fn tail_prints_last_lines() {
+        let input: String = (1..=20).map(|i| format!("{i}\n")).collect();
+        assert_eq!(tail(&["-n", "2"], &input), "19\n20\n");
+        assert_eq!(tail(&[], &input).lines().count(), DEFAULT_LINE_COUNT);
+        assert_eq!(tail(&["-n", "0"], &input), "");
+    }
+
+    #[test]
+    fn invalid_line_count_is_an_error() {
+        assert!(Head::new(&["-n".to_string(), "x".to_string()]).is_err());
+        assert!(Tail::new(&["-n".to_string()]).is_err());
+    }
+
+    #[test]
+    fn unknown_options_are_errors() {
+        let error = Head::new(&["-c".to_string(), "5".to_string()])
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "head: invalid option -- 'c'");
+        assert!(Tail::new(&["-f".to_string()]).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exit_codes_wrap_around() {
+        assert_eq!(exit_code(256), 0);
+        assert_eq!(exit_code(257), 1);
+        assert_eq!(exit_code(-1), 255);
+        assert_eq!(exit_code(4294967297), 1);
+    }
+
+    #[test]
+    fn wait_parses_job_specs_and_pids() {
+        let args = ["%2".to_string(), "1234".to_string()];
+        let wait = Wait::new(&args).unwrap();
+        assert_eq!(wait.jobs, [WaitFor::Job(2), WaitFor::Pid(1234)]);
+        assert!(Wait::new(&["%x".to_string()]).is_err());
+        assert!(Wait::new(&["-1".to_string()]).is_err());
+    }
+
+    #[test]
+    fn disown_parses_job_specs() {
+        let args = ["-h".to_string(), "%2".to_string(), "3".to_string()];
+        let disown = Disown::new(&args).unwrap();
+        assert!(disown.nohup);
+        assert_eq!(disown.ids, [2, 3]);
+        assert!(Disown::new(&["%x".to_string()]).is_err());
+    }
+
+    #[test]
+    fn read_consumes_a_single_line() {
+        let args = ["READ_TEST_A".to_string(), "READ_TEST_B".to_string()];
+        let mut input = "one two  three\nnext\n".as_bytes();
+        let mut variables = Variables::new();
+        let read = ReadLine::new(&args).unwrap();
+        let output = read.run(&mut input, &mut variables).unwrap();
+        assert!(output.unwrap().status.success());
+        assert_eq!(variables.get("READ_TEST_A").unwrap(), "one");
+        assert_eq!(variables.get("READ_TEST_B").unwrap(), "two  three");
+        assert!(std::env::var_os("READ_TEST_A").is_none());
+        assert_eq!(input, b"next\n");
+
+        assert!(ReadLine::new(&["1x".to_string()]).is_err());
+    }
+
+    fn tr(args: &[&str], input: &str) -> String {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let output = Tr::new(&args).unwrap().run(input.as_bytes()).unwrap();
+        String::from_utf8(output.unwrap().stdout).unwrap()
+    }
+
+    #[test]
+    fn tr_translates_ranges() {
+        assert_eq!(tr(&["a-z", "A-Z"], "hello, world\n"), "HELLO, WORLD\n");
+        assert_eq!(tr(&["abc", "x"], "abcd"), "xxxd");
+        assert_eq!(tr(&["a-", "_+"], "a-b"), "_+b");
+    }
+
+    #[test]
+    fn tr_deletes_characters() {
+        assert_eq!(tr(&["-d", "."], "h.e.l\n"), "hel\n");
+        assert_eq!(tr(&["-d", "0-9"], "a1b22c"), "abc");
+    }
+
+    /// Returns the input in chunks of one byte, like a slow pipe.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+            let Some((first, rest)) = self.0.split_first() else {
+                return Ok(0);
+            };
+            buffer[0] = *first;
+            self.0 = rest;
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn tr_keeps_characters_split_between_chunks() {
+        let tr = Tr::new(&["ä".to_string(), "a".to_string()]).unwrap();
+        let mut stdout = Vec::new();
+        tr.translate(Trickle("jäger €".as_bytes()), &mut stdout)
+            .unwrap();
+        assert_eq!(stdout, "jager €".as_bytes());
+    }
+
+    #[test]
+    fn tr_keeps_invalid_utf8() {
+        let tr = Tr::new(&["a".to_string(), "b".to_string()]).unwrap();
+        let mut stdout = Vec::new();
+        tr.translate(&b"a\xffa\xe2"[..], &mut stdout).unwrap();
+        assert_eq!(stdout, b"b\xffb\xe2");
+    }
+
+    #[test]
+    fn cd_parses_options() {
+        let args = ["-L".to_string(), "-P".to_string(), "dir".to_string()];
+        let cd = Cd::new(&args).unwrap();
+        assert!(cd.physical);
+        assert_eq!(cd.dir, PathBuf::from("dir"));
+        assert_eq!(Cd::new(&["-".to_string()]).unwrap().dir, PathBuf::from("-"));
+        assert!(Cd::new(&["-".to_string()]).unwrap().back);
+        assert!(Cd::new(&["-x".to_string()]).is_err());
+    }
+
+    #[test]
+    fn set_takes_option_names_after_o() {
+        let args = ["-eo".to_string(), "pipefail".to_string(), "+x".to_string()];
+        let set = Set::new(&args).unwrap();
+        assert_eq!(
+            set.changes,
+            [("errexit", true), ("pipefail", true), ("xtrace", false)]
+        );
+        assert!(Set::new(&["-o".to_string()]).is_err());
+        assert!(Set::new(&["-o".to_string(), "nonsense".to_string()]).is_err());
+    }
+
+    #[test]
+    fn cd_treats_arguments_after_double_dash_as_paths() {
+        let args = ["--".to_string(), "-".to_string()];
+        let cd = Cd::new(&args).unwrap();
+        assert_eq!(cd.dir, PathBuf::from("-"));
+        assert!(!cd.back);
+        let args = ["--".to_string(), "-P".to_string()];
+        let cd = Cd::new(&args).unwrap();
+        assert_eq!(cd.dir, PathBuf::from("-P"));
+        assert!(!cd.physical);
+    }
+
+    #[test]
+    fn logical_path_removes_dots() {
+        let current = Path::new("/a/link");
+        assert_eq!(
+            logical_path(Some(current), Path::new("../b/./c")),
+            PathBuf::from("/a/b/c")
+        );
+        assert_eq!(
+            logical_path(Some(current), Path::new("/x/..")),
+            PathBuf::from("/")
+        );
+    }
+
+    fn echo(args: &[&str]) -> String {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let output = Echo::new(&args).run().unwrap();
+        String::from_utf8(output.unwrap().stdout).unwrap()
+    }
+
+    #[test]
+    fn echo_joins_with_separator() {
+        assert_eq!(echo(&["a", "b"]), "a b\n");
+        assert_eq!(echo(&["-s", ",", "a", "b", "c"]), "a,b,c\n");
+        assert_eq!(echo(&["-n", "-s", "", "a", "b"]), "ab");
+    }
+
+    #[test]
+    fn echo_options_look_like_data() {
+        assert_eq!(echo(&["a", "-s", ","]), "a -s ,\n");
+        assert_eq!(echo(&["--", "-n", "-s"]), "-n -s\n");
+        assert_eq!(echo(&["-s"]), "-s\n");
+    }
+
+    #[test]
+    fn env_parses_variables_and_command() {
+        let args: Vec<String> = ["-i", "A=1", "B=x=y", "cmd", "C=2"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let env = Env::new(&args).unwrap();
+        assert!(env.ignore_environment);
+        assert_eq!(
+            env.vars,
+            [
+                ("A".to_string(), "1".to_string()),
+                ("B".to_string(), "x=y".to_string())
+            ]
+        );
+        assert_eq!(env.command, ["cmd", "C=2"]);
+    }
+
+    #[test]
+    fn env_ignoring_environment_prints_only_given_variables() {
+        let args = ["-i".to_string(), "FOO=bar".to_string()];
+        let output = Env::new(&args).unwrap().run().unwrap().unwrap();
+        assert_eq!(output.stdout, b"FOO=bar\n");
+    }
+
+    #[test]
+    fn type_finds_builtins() {
+        let output = Type::new(vec!["cd".to_string()]).run().unwrap().unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"cd is a shell builtin\n");
+    }
+
+    #[test]
+    fn type_fails_for_unknown_commands() {
+        let name = "this-command-does-not-exist".to_string();
+        let output = Type::new(vec![name]).run().unwrap().unwrap();
+        assert!(!output.status.success());
+        assert_eq!(
+            output.stderr,
+            b"type: this-command-does-not-exist: not found\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn find_in_path_searches_directories_in_order() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("rush-type-{}", std::process::id()));
+        let (first, second) = (dir.join("first"), dir.join("second"));
+        std::fs::create_dir_all(&first).unwrap();
+        std::fs::create_dir_all(&second).unwrap();
+        // Files which aren't executable are skipped.
+        std::fs::write(first.join("tool"), "").unwrap();
+        std::fs::write(second.join("tool"), "").unwrap();
+        let permissions = std::fs::Permissions::from_mode(0o755);
+        std::fs::set_permissions(second.join("tool"), permissions).unwrap();
+
+        let path = std::env::join_paths([&first, &second]).unwrap();
+        assert_eq!(find_in_path("tool", &path), Some(second.join("tool")));
+        assert_eq!(find_in_path("missing", &path), None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn clear_only_writes_to_terminals() {
+        assert_eq!(clear_sequence(true), b"\x1b[2J\x1b[H");
+        assert_eq!(clear_sequence(false), b"");
+    }
+
+    #[test]
+    fn help_lists_all_builtins() {
+        let output = Help.run().unwrap().unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let builtins = BUILTINS
+            .iter()
+            .map(|builtin| (builtin.name, builtin.description));
+        for (name, description) in builtins.chain(KEYWORDS.iter().copied()) {
+            assert!(stdout.contains(&format!("{name} ")), "{name} is missing");
+            assert!(stdout.contains(description));
+        }
+    }
+
+    #[test]
+    fn type_tells_keywords_and_builtins_apart() {
+        let names = vec!["time".to_string(), "cd".to_string()];
+        let output = Type::new(names).run().unwrap().unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(stdout, "time is a shell keyword\ncd is a shell builtin\n");
+    }
+
+    #[test]
+    fn sort_rejects_unknown_flags() {
+        assert!(Sort::new(&["-x".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,203 @@
+//! Tab completion for commands and paths.
+
+use std::{collections::HashMap, env, ffi::OsStr, fs, path::Path};
+
+/// Maps command names to the words their first argument completes to,
+/// as registered with the `complete` builtin.
+pub type Completions = HashMap<String, Vec<String>>;
+
+/// Complete `token` to the paths of files and directories which start with it.
+///
+/// Directories end with a `/`, so the user can continue with the next component.
+/// Hidden files are only suggested if `token` starts with a `.`.
+pub fn complete(token: &str) -> Vec<String> {
+    // Split `src/ma` into the directory `src/` and the prefix `ma`.
+    let (dir, prefix) = match token.rfind('/') {
+        Some(i) => (&token[..=i], &token[i + 1..]),
+        None => ("", token),
+    };
+    let Ok(entries) = fs::read_dir(if dir.is_empty() { "." } else { dir }) else {
+        return Vec::new();
+    };
+
+    let mut candidates: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            // Follow symbolic links, so a link to a directory behaves like one.
+            let is_dir = fs::metadata(entry.path()).is_ok_and(|m| m.is_dir());
+            let suffix = if is_dir { "/" } else { "" };
+            Some(format!("{dir}{name}{suffix}"))
+        })
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+/// Complete `token` to the names of executables in `$PATH`.
+///
+/// If `token` contains a `/`, it is a path to the command instead,
+/// e.g. `./script.sh`, and is completed like any other path.
+pub fn complete_command(token: &str) -> Vec<String> {
+    if token.contains('/') {
+        return complete(token);
+    }
+    match env::var_os("PATH") {
+        Some(path) => complete_command_in(token, &path),
+        None => Vec::new(),
+    }
+}
+
+/// Complete `token` to the names of executables in the directories of `path`.
+fn complete_command_in(token: &str, path: &OsStr) -> Vec<String> {
+    let mut candidates: Vec<String> = env::split_paths(path)
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| is_executable(&entry.path()))
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| name.starts_with(token))
+        .collect();
+    candidates.sort();
+    // The same command can be in more than one directory.
+    candidates.dedup();
+    candidates
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Complete `token`, the first argument of `command`, to the words registered for `command`.
+///
+/// Returns `None` if nothing is registered for `command`, so its arguments are completed as paths.
+pub fn complete_registered(
+    completions: &Completions,
+    command: &str,
+    token: &str,
+) -> Option<Vec<String>> {
+    let words = completions.get(command)?;
+    Some(
+        words
+            .iter()
+            .filter(|word| word.starts_with(token))
+            .cloned()
+            .collect(),
+    )
+}
+
+/// The longest prefix which all `candidates` share.
+pub fn common_prefix(candidates: &[String]) -> &str {
+    let Some(first) = candidates.first() else {
+        return "";
+    };
+    let mut prefix = first.as_str();
+    for candidate in &candidates[1..] {
+        let len = prefix
+            .char_indices()
+            .zip(candidate.chars())
+            .take_while(|((_, a), b)| a == b)
+            .last()
+            .map_or(0, |((i, a), _)| i + a.len_utf8());
+        prefix = &prefix[..len];
+    }
+    prefix
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Create a directory with some files and directories to complete.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("completion_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("folder")).unwrap();
+        fs::write(dir.join("foo.txt"), "").unwrap();
+        fs::write(dir.join("bar.txt"), "").unwrap();
+        fs::write(dir.join(".hidden"), "").unwrap();
+        dir
+    }
+
+    #[test]
+    fn files_and_directories_are_completed() {
+        let dir = temp_dir("files");
+        let dir = dir.to_str().unwrap();
+        assert_eq!(
+            complete(&format!("{dir}/fo")),
+            vec![format!("{dir}/folder/"), format!("{dir}/foo.txt")]
+        );
+        assert_eq!(
+            complete(&format!("{dir}/b")),
+            vec![format!("{dir}/bar.txt")]
+        );
+        assert!(complete(&format!("{dir}/x")).is_empty());
+    }
+
+    #[test]
+    fn hidden_files_need_a_dot() {
+        let dir = temp_dir("hidden");
+        let dir = dir.to_str().unwrap();
+        assert_eq!(complete(&format!("{dir}/")).len(), 3);
+        assert_eq!(
+            complete(&format!("{dir}/.")),
+            vec![format!("{dir}/.hidden")]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn commands_are_completed_from_path() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir("commands");
+        let script = dir.join("my-command");
+        fs::write(&script, "").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+        // Don't change `PATH` of the test process, other tests might need it.
+        let path = env::join_paths([&dir, &dir.join("folder")]).unwrap();
+        assert_eq!(
+            complete_command_in("my", &path),
+            vec!["my-command".to_string()]
+        );
+        // `foo.txt` is not executable.
+        assert!(complete_command_in("foo", &path).is_empty());
+    }
+
+    #[test]
+    fn registered_words_are_completed() {
+        let mut completions = Completions::new();
+        let words = ["status", "stash", "commit"].map(String::from).to_vec();
+        completions.insert("gitcmd".to_string(), words);
+        assert_eq!(
+            complete_registered(&completions, "gitcmd", "sta"),
+            Some(vec!["status".to_string(), "stash".to_string()])
+        );
+        assert_eq!(
+            complete_registered(&completions, "gitcmd", "x"),
+            Some(vec![])
+        );
+        assert_eq!(complete_registered(&completions, "ls", "sta"), None);
+    }
+
+    #[test]
+    fn common_prefix_of_candidates() {
+        let candidates = ["folder/".to_string(), "foo.txt".to_string()];
+        assert_eq!(common_prefix(&candidates), "fo");
+        assert_eq!(common_prefix(&candidates[..1]), "folder/");
+        assert_eq!(common_prefix(&[]), "");
+    }
+}
@@ -0,0 +1,871 @@
+//! Expansions which are applied to the words of a command before it runs.
+//!
+//! The parser keeps quotes in the words, so that expansions can tell
+//! quoted and unquoted text apart. Like in bash, the expansions run in a fixed order:
+//!
+//! 1. Brace expansion: `file{1,2}.txt` becomes the two words `file1.txt` and `file2.txt`.
+//!    Sequences like `{1..3}` or `{a..c}` become one word for each element.
+//! 2. Tilde expansion: `~/src` becomes `$HOME/src`.
+//! 3. Parameter expansion: `$NAME` and `${NAME}` are replaced with the value of the variable.
+//!    `${NAME[i]}` is the word at index `i` of the value.
+//!    Arithmetic expansion, `$((1 + 2))`, happens at the same time, see [`crate::arithmetic`],
+//!    and so does command substitution: `$(cmd)` or `` `cmd` `` is replaced with the output of `cmd`.
+//! 4. Word splitting: the results of unquoted expansions are split on whitespace.
+//! 5. Pathname expansion: unquoted words with `*`, `?` or `[` are replaced with matching files.
+//! 6. Quote removal: the quotes and backslashes themselves are removed.
+//!
+//! The order matters. With `x='*'`, `echo $x` lists the files of the current directory,
+//! while `echo "$x"` prints `*`, because the quotes protect the value from globbing.
+
+use crate::{
+    arithmetic,
+    parser::{Cmd, Redirect},
+    variables::Variables,
+    Result,
+};
+use std::{
+    iter::Peekable,
+    path::{Path, PathBuf},
+    str::{CharIndices, Chars},
+};
+
+impl Cmd {
+    /// Apply all expansions to the words of the command.
+    ///
+    /// A word can expand to several words, or to none at all.
+    /// So the binary is the first word after the expansion.
+    pub fn expand(&self, variables: &Variables) -> Result<Cmd> {
+        let mut words = Vec::new();
+        for word in std::iter::once(&self.binary).chain(&self.args) {
+            words.extend(expand(word, variables)?);
+        }
+        let mut words = words.into_iter();
+        Ok(Cmd {
+            // Variable assignments and redirections are neither split nor globbed.
+            assignments: self
+                .assignments
+                .iter()
+                .map(|(name, value)| Ok((name.clone(), expand_word(value, variables)?)))
+                .collect::<Result<_>>()?,
+            binary: words.next().unwrap_or_default(),
+            args: words.collect(),
+            redirects: self
+                .redirects
+                .iter()
+                .map(|redirect| {
+                    Ok(Redirect {
+                        kind: redirect.kind,
+                        target: if redirect.kind.is_here_document() {
+                            expand_here_document(&redirect.target, variables)?
+                        } else {
+                            expand_word(&redirect.target, variables)?
+                        },
+                    })
+                })
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+/// Where a character of an expanded word comes from.
+///
+/// Quote removal happens right away, so this is how later expansions
+/// can still tell which characters were quoted.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Origin {
+    /// Quoted or escaped. These characters are never special.
+    Quoted,
+    /// Written as it is, outside of quotes. Special for pathname expansion.
+    Unquoted,
+    /// The result of an unquoted expansion. Special for word splitting and pathname expansion.
+    Expansion,
+}
+
+/// A word during expansion: each character with its origin.
+type Field = Vec<(char, Origin)>;
+
+/// Apply all expansions to `word`, in the order described in the module documentation.
+pub fn expand(word: &str, variables: &Variables) -> Result<Vec<String>> {
+    let mut fields = Vec::new();
+    for word in expand_braces(word) {
+        let (chars, quoted) = expand_parameters(&word, variables)?;
+        fields.extend(
+            split_fields(chars, quoted)
+                .into_iter()
+                .flat_map(expand_pathnames),
+        );
+    }
+    Ok(fields)
+}
+
+/// Replace the first unquoted `{a,b}` or `{1..3}` in `word` with each alternative,
+/// then expand the results again.
+///
+/// This produces every combination: `{a,b}{1,2}` becomes `a1 a2 b1 b2`.
+/// Braces without a comma or a valid sequence, like `{}`, `{a}` or `{1..b}`,
+/// and `${NAME}` are kept as they are.
+/// The quotes stay in the words, they are removed by the later expansions.
+fn expand_braces(word: &str) -> Vec<String> {
+    let mut chars = word.char_indices();
+    let mut previous = None;
+    while let Some((start, c)) = chars.next() {
+        match c {
+            '\\' | '\'' | '"' => skip_quoted(c, &mut chars),
+            '{' if previous != Some('$') => {
+                let braces = &word[start..];
+                let alternatives = brace_sequence(braces).or_else(|| brace_alternatives(braces));
+                if let Some((alternatives, end)) = alternatives {
+                    let (prefix, suffix) = (&word[..start], &word[start + end..]);
+                    return alternatives
+                        .into_iter()
+                        .flat_map(|alternative| {
+                            expand_braces(&format!("{prefix}{alternative}{suffix}"))
+                        })
+                        .collect();
+                }
+            }
+            _ => {}
+        }
+        previous = Some(c);
+    }
+    vec![word.to_string()]
+}
+
+/// Expand the sequence at the start of `text`: `{1..5}`, `{5..1}`, `{a..e}` or `{1..10..2}`.
+///
+/// Numbers with a leading zero, like `{01..10}`, are padded to the same width.
+/// Also returns the length of the braces, including the closing `}`.
+fn brace_sequence(text: &str) -> Option<(Vec<String>, usize)> {
+    let end = text.find('}')?;
+    let parts: Vec<&str> = text[1..end].split("..").collect();
+    let (first, last, step) = match parts[..] {
+        [first, last] => (first, last, 1),
+        [first, last, step] => (first, last, step.parse::<i64>().ok()?.unsigned_abs().max(1)),
+        _ => return None,
+    };
+    let elements = match (first.parse::<i64>(), last.parse::<i64>()) {
+        (Ok(first_number), Ok(last_number)) => {
+            let has_leading_zero = |n: &str| {
+                let digits = n.trim_start_matches('-');
+                digits.len() > 1 && digits.starts_with('0')
+            };
+            let width = if has_leading_zero(first) || has_leading_zero(last) {
+                first.len().max(last.len())
+            } else {
+                0
+            };
+            sequence(first_number, last_number, step)
+                .map(|n| format!("{n:0width$}"))
+                .collect()
+        }
+        _ => {
+            let letter = |text: &str| match text.as_bytes() {
+                [c] if c.is_ascii_alphabetic() => Some(i64::from(*c)),
+                _ => None,
+            };
+            sequence(letter(first)?, letter(last)?, step)
+                .map(|c| char::from(c as u8).to_string())
+                .collect()
+        }
+    };
+    Some((elements, end + 1))
+}
+
+/// The numbers from `first` to `last`, counting up or down by `step`.
+fn sequence(first: i64, last: i64, step: u64) -> impl Iterator<Item = i64> {
+    let count = first.abs_diff(last) / step;
+    let step = if first <= last {
+        step as i64
+    } else {
+        -(step as i64)
+    };
+    (0..=count as i64).map(move |i| first + i * step)
+}
+
+/// Skip the rest of the quotes or the escaped character started by `c`.
+fn skip_quoted(c: char, chars: &mut CharIndices) {
+    match c {
+        '\\' => {
+            chars.next();
+        }
+        '\'' => {
+            chars.find(|&(_, c)| c == '\'');
+        }
+        _ => {
+            while let Some((_, c)) = chars.next() {
+                match c {
+                    '"' => break,
+                    '\\' => {
+                        chars.next();
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// Split the braces at the start of `text` into their comma-separated alternatives.
+///
+/// Also returns the length of the braces, including the closing `}`.
+/// Returns `None` if the braces aren't closed or don't contain a comma.
+fn brace_alternatives(text: &str) -> Option<(Vec<String>, usize)> {
+    let mut alternatives = Vec::new();
+    let mut depth = 0;
+    let mut alternative_start = 1;
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' | '\'' | '"' => skip_quoted(c, &mut chars),
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    if alternatives.is_empty() {
+                        return None;
+                    }
+                    alternatives.push(text[alternative_start..i].to_string());
+                    return Some((alternatives, i + 1));
+                }
+            }
+            ',' if depth == 1 => {
+                alternatives.push(text[alternative_start..i].to_string());
+                alternative_start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Expand the tilde and the variables in `word` and remove quotes and backslashes.
+///
+/// Unlike [`expand`], the result is a single word which is neither split nor globbed.
+/// This is how the values of variable assignments and the files of redirections are expanded.
+pub fn expand_word(word: &str, variables: &Variables) -> Result<String> {
+    Ok(expand_parameters(word, variables)?
+        .0
+        .into_iter()
+        .map(|(c, _)| c)
+        .collect())
+}
+
+/// Parameter expansion of the text of a here-document.
+///
+/// The text is treated like a word in double quotes, except that `"` isn't special.
+/// So the result is neither split nor globbed, and a backslash only escapes
+/// `\`, `$` and `` ` ``.
+fn expand_here_document(text: &str, variables: &Variables) -> Result<String> {
+    let mut expanded = Field::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next_if(|c| matches!(c, '\\' | '$' | '`')) {
+                Some(escaped) => expanded.push((escaped, Origin::Quoted)),
+                None => expanded.push(('\\', Origin::Quoted)),
+            },
+            '$' => expand_variable(&mut chars, &mut expanded, Origin::Quoted, variables)?,
+            '`' => {
+                let output = substitute_command(&backquoted(&mut chars), variables)?;
+                expanded.extend(output.chars().map(|c| (c, Origin::Quoted)));
+            }
+            c => expanded.push((c, Origin::Quoted)),
+        }
+    }
+    Ok(expanded.into_iter().map(|(c, _)| c).collect())
+}
+
+/// Tilde and parameter expansion, and quote removal.
+/// Also returns whether the word contained quotes.
+///
+/// - Inside single quotes, every character is taken literally.
+/// - Inside double quotes, variables are expanded and a backslash
+///   only escapes `"`, `\`, `$` and `` ` ``.
+/// - Outside of quotes, variables are expanded and a backslash escapes any character.
+fn expand_parameters(word: &str, variables: &Variables) -> Result<(Field, bool)> {
+    let mut expanded = Field::with_capacity(word.len());
+    let mut quoted = false;
+    let mut chars = word.chars().peekable();
+    expand_tilde(&mut chars, &mut expanded);
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                quoted = true;
+                let literal = chars.by_ref().take_while(|&c| c != '\'');
+                expanded.extend(literal.map(|c| (c, Origin::Quoted)));
+            }
+            '"' => {
+                quoted = true;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => match chars.next_if(|c| matches!(c, '"' | '\\' | '$' | '`')) {
+                            Some(escaped) => expanded.push((escaped, Origin::Quoted)),
+                            None => expanded.push(('\\', Origin::Quoted)),
+                        },
+                        '$' => {
+                            expand_variable(&mut chars, &mut expanded, Origin::Quoted, variables)?
+                        }
+                        '`' => {
+                            let output = substitute_command(&backquoted(&mut chars), variables)?;
+                            expanded.extend(output.chars().map(|c| (c, Origin::Quoted)));
+                        }
+                        c => expanded.push((c, Origin::Quoted)),
+                    }
+                }
+            }
+            '\\' => {
+                quoted = true;
+                expanded.extend(chars.next().map(|c| (c, Origin::Quoted)));
+            }
+            '$' => expand_variable(&mut chars, &mut expanded, Origin::Expansion, variables)?,
+            '`' => {
+                let output = substitute_command(&backquoted(&mut chars), variables)?;
+                expanded.extend(output.chars().map(|c| (c, Origin::Expansion)));
+            }
+            c => expanded.push((c, Origin::Unquoted)),
+        }
+    }
+    Ok((expanded, quoted))
+}
+
+/// Replace a `~` at the start of the word with the home directory,
+/// if it is followed by a `/` or stands on its own.
+fn expand_tilde(chars: &mut Peekable<Chars>, expanded: &mut Field) {
+    if chars.peek() != Some(&'~') {
+        return;
+    }
+    let mut rest = chars.clone();
+    rest.next();
+    if !matches!(rest.peek(), None | Some('/')) {
+        return;
+    }
+    if let Some(home) = std::env::home_dir() {
+        chars.next();
+        // The home directory isn't split or globbed, even if it contains spaces.
+        let home = home.to_string_lossy().into_owned();
+        expanded.extend(home.chars().map(|c| (c, Origin::Quoted)));
+    }
+}
+
+/// Expand `$NAME`, `${NAME}`, `${NAME[i]}`, `$((expression))` or `$(command)` right after the `$`.
+/// The characters of the value get the given `origin`.
+///
+/// Unset variables expand to nothing. A `$` which isn't followed by a name is kept.
+fn expand_variable(
+    chars: &mut Peekable<Chars>,
+    expanded: &mut Field,
+    origin: Origin,
+    variables: &Variables,
+) -> Result<()> {
+    if chars.clone().take(2).eq(['(', '(']) {
+        let expression = expand_word(&arithmetic_expression(chars), variables)?;
+        let value = arithmetic::evaluate(&expression, variables)?;
+        expanded.extend(value.to_string().chars().map(|c| (c, origin)));
+        return Ok(());
+    }
+    if chars.peek() == Some(&'(') {
+        let output = substitute_command(&parenthesized(chars), variables)?;
+        expanded.extend(output.chars().map(|c| (c, origin)));
+        return Ok(());
+    }
+    let braced = chars.next_if_eq(&'{').is_some();
+    let mut name = String::new();
+    while let Some(c) = chars.next_if(|&c| c.is_ascii_alphanumeric() || c == '_') {
+        // Names can't start with a digit.
+        if name.is_empty() && c.is_ascii_digit() {
+            name.push(c);
+            break;
+        }
+        name.push(c);
+    }
+    // `${NAME[1]}` is the second word of the value. This is enough for `${PIPESTATUS[1]}`.
+    let mut index = None;
+    if braced && chars.next_if_eq(&'[').is_some() {
+        let digits: String = std::iter::from_fn(|| chars.next_if(char::is_ascii_digit)).collect();
+        chars.next_if_eq(&']');
+        index = Some(digits.parse().unwrap_or(0));
+    }
+    if braced {
+        chars.next_if_eq(&'}');
+    } else if name.is_empty() {
+        // A lone `$` is an ordinary character.
+        let origin = if origin == Origin::Quoted {
+            Origin::Quoted
+        } else {
+            Origin::Unquoted
+        };
+        expanded.push(('$', origin));
+        return Ok(());
+    }
+    let Some(value) = variables.get(&name) else {
+        return Ok(());
+    };
+    let value = match index {
+        Some(index) => value.split_whitespace().nth(index).unwrap_or_default(),
+        None => &value,
+    };
+    expanded.extend(value.chars().map(|c| (c, origin)));
+    Ok(())
+}
+
+/// Take the expression of `((expression))`, up to the matching `))`.
+///
+/// Variables in the expression, like `$x`, are expanded before it is evaluated.
+fn arithmetic_expression(chars: &mut Peekable<Chars>) -> String {
+    chars.nth(1);
+    let mut expression = String::new();
+    let mut depth = 0;
+    while let Some(c) = chars.next() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 && chars.next_if_eq(&')').is_some() => break,
+            ')' => depth -= 1,
+            _ => {}
+        }
+        expression.push(c);
+    }
+    expression
+}
+
+/// Take the command of `(command)`, up to the matching `)`.
+/// Parentheses inside quotes, like in `$(echo ')')`, don't count.
+fn parenthesized(chars: &mut Peekable<Chars>) -> String {
+    chars.next();
+    let mut command = String::new();
+    let mut depth = 0;
+    let mut quote = None;
+    for c in chars.by_ref() {
+        match (c, quote) {
+            ('\'' | '"', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            ('(', None) => depth += 1,
+            (')', None) if depth == 0 => break,
+            (')', None) => depth -= 1,
+            _ => {}
+        }
+        command.push(c);
+    }
+    command
+}
+
+/// Take the command of `` `command` ``, up to the closing backtick.
+/// A backslash escapes a backtick, `$` or another backslash.
+fn backquoted(chars: &mut Peekable<Chars>) -> String {
+    let mut command = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '`' => break,
+            '\\' => match chars.next_if(|c| matches!(c, '`' | '$' | '\\')) {
+                Some(escaped) => command.push(escaped),
+                None => command.push(c),
+            },
+            c => command.push(c),
+        }
+    }
+    command
+}
+
+/// Run `command` in a new shell and return its stdout without the trailing newlines.
+///
+/// The command runs in its own process, like a subshell, so `$(cd /)`
+/// doesn't change the current directory of this shell.
+/// The variables of this shell are assigned in front of the command,
+/// so the subshell has them too, without exporting them.
+fn substitute_command(command: &str, variables: &Variables) -> Result<String> {
+    let mut line = String::new();
+    for (name, value) in variables.local() {
+        line.push_str(&format!("{name}='{}'; ", value.replace('\'', r"'\''")));
+    }
+    line.push_str(command);
+    let output = std::process::Command::new(std::env::current_exe()?)
+        .args(["-c", &line])
+        .stderr(std::process::Stdio::inherit())
+        .output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout.trim_end_matches('\n').to_string())
+}
+
+/// Split the results of unquoted expansions on whitespace.
+///
+/// A word which is empty after the expansion is removed, unless it contained quotes.
+/// This way, `$UNSET` disappears, but `""` stays an empty argument.
+fn split_fields(chars: Field, quoted: bool) -> Vec<Field> {
+    let mut fields = Vec::new();
+    let mut field = Field::new();
+    let mut field_quoted = quoted;
+    for (c, origin) in chars {
+        if origin == Origin::Expansion && c.is_ascii_whitespace() {
+            if !field.is_empty() || field_quoted {
+                fields.push(std::mem::take(&mut field));
+            }
+            // Only the first field contains the quotes, e.g. `"a"$X`.
+            field_quoted = false;
+        } else {
+            field.push((c, origin));
+        }
+    }
+    if !field.is_empty() || field_quoted {
+        fields.push(field);
+    }
+    fields
+}
+
+/// Whether `c` is special in a glob pattern.
+fn is_glob_char((c, origin): (char, Origin)) -> bool {
+    origin != Origin::Quoted && matches!(c, '*' | '?' | '[')
+}
+
+/// Replace `field` with the sorted paths which match it, if it is a glob pattern.
+/// If nothing matches, the pattern is kept as it is, like in bash.
+fn expand_pathnames(field: Field) -> Vec<String> {
+    let text = || field.iter().map(|&(c, _)| c).collect();
+    if !field.iter().copied().any(is_glob_char) {
+        return vec![text()];
+    }
+
+    let absolute = field.first().is_some_and(|&(c, _)| c == '/');
+    let mut paths = vec![if absolute {
+        PathBuf::from("/")
+    } else {
+        PathBuf::new()
+    }];
+    for component in field.split(|&(c, _)| c == '/') {
+        if component.is_empty() {
+            continue;
+        }
+        paths = paths
+            .iter()
+            .flat_map(|path| expand_component(path, component))
+            .collect();
+    }
+    if paths.is_empty() {
+        return vec![text()];
+    }
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect()
+}
+
+/// The paths inside `dir` which match one `component` of a pattern.
+fn expand_component(dir: &Path, component: &[(char, Origin)]) -> Vec<PathBuf> {
+    if !component.iter().copied().any(is_glob_char) {
+        let name: String = component.iter().map(|&(c, _)| c).collect();
+        let path = dir.join(name);
+        // Without a glob, the path must exist to match, e.g. `src/*.rs` needs `src`.
+        return if path.symlink_metadata().is_ok() {
+            vec![path]
+        } else {
+            vec![]
+        };
+    }
+
+    let read_dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let Ok(entries) = std::fs::read_dir(read_dir) else {
+        return vec![];
+    };
+    let mut matches: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        // Hidden files only match if the pattern starts with a dot.
+        .filter(|name| !name.starts_with('.') || component[0].0 == '.')
+        .filter(|name| matches_pattern(component, &name.chars().collect::<Vec<_>>()))
+        .map(|name| dir.join(name))
+        .collect();
+    matches.sort();
+    matches
+}
+
+/// Whether `name` matches the glob `pattern`.
+///
+/// - `*` matches any sequence of characters.
+/// - `?` matches any single character.
+/// - `[abc]`, `[a-z]` match one of the characters, `[!abc]` any other character.
+fn matches_pattern(pattern: &[(char, Origin)], name: &[char]) -> bool {
+    let Some((&first, rest)) = pattern.split_first() else {
+        return name.is_empty();
+    };
+    if !is_glob_char(first) {
+        return name.first() == Some(&first.0) && matches_pattern(rest, &name[1..]);
+    }
+    match first.0 {
+        '*' => (0..=name.len()).any(|skip| matches_pattern(rest, &name[skip..])),
+        '?' => !name.is_empty() && matches_pattern(rest, &name[1..]),
+        _ => match (Bracket::parse(rest), name.split_first()) {
+            (Some((bracket, rest)), Some((&c, name))) => {
+                bracket.matches(c) && matches_pattern(rest, name)
+            }
+            (Some(_), None) => false,
+            // Without a closing `]`, the `[` is an ordinary character.
+            (None, _) => name.first() == Some(&'[') && matches_pattern(rest, &name[1..]),
+        },
+    }
+}
+
+/// A bracket expression of a glob pattern, like `[a-z]` or `[!abc]`.
+struct Bracket<'a> {
+    /// The characters and ranges between the brackets.
+    set: &'a [(char, Origin)],
+    /// `[!...]` matches the characters which are not in the set.
+    negated: bool,
+}
+
+impl<'a> Bracket<'a> {
+    /// Parse the bracket expression after a `[`, like `a-z]`.
+    /// Returns the expression and the rest of the pattern after the `]`.
+    fn parse(pattern: &'a [(char, Origin)]) -> Option<(Self, &'a [(char, Origin)])> {
+        let negated = matches!(pattern.first(), Some(('!' | '^', _)));
+        let start = usize::from(negated);
+        // A `]` right at the start is part of the set, as in `[]a]`.
+        let end = start
+            + 1
+            + pattern
+                .get(start + 1..)?
+                .iter()
+                .position(|&(c, _)| c == ']')?;
+        let bracket = Self {
+            set: &pattern[start..end],
+            negated,
+        };
+        Some((bracket, &pattern[end + 1..]))
+    }
+
+    fn matches(&self, c: char) -> bool {
+        let mut found = false;
+        let mut set = self.set;
+        while let Some(&(first, _)) = set.first() {
+            match set {
+                [_, ('-', _), (last, _), rest @ ..] => {
+                    found |= (first..=*last).contains(&c);
+                    set = rest;
+                }
+                _ => {
+                    found |= first == c;
+                    set = &set[1..];
+                }
+            }
+        }
+        found != self.negated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The variables of the tests. They are not exported, so the tests don't affect each other.
+    fn variables() -> Variables {
+        let mut variables = Variables::new();
+        variables.set("EXPAND_TEST_VAR", "value");
+        variables.set("EXPAND_TEST_SPLIT", " a  b ");
+        variables.set("EXPAND_TEST_INDEX", "0 1 0");
+        variables.set("EXPAND_TEST_NUMBER", "3");
+        variables
+    }
+
+    fn word(word: &str) -> String {
+        expand_word(word, &variables()).unwrap()
+    }
+
+    #[test]
+    fn single_quotes_are_literal() {
+        assert_eq!(word(r#"'a "b" \c'"#), r#"a "b" \c"#);
+    }
+
+    #[test]
+    fn double_quotes_keep_unknown_escapes() {
+        assert_eq!(word(r#""a \"b\" \c""#), r#"a "b" \c"#);
+    }
+
+    #[test]
+    fn backslash_escapes_outside_of_quotes() {
+        assert_eq!(word(r"a\ b\\c"), r"a b\c");
+    }
+
+    #[test]
+    fn quotes_in_the_middle_of_a_word_are_removed() {
+        assert_eq!(word(r#"a'b'"c"''d"#), "abcd");
+    }
+
+    #[test]
+    fn variables_are_expanded() {
+        assert_eq!(word("$EXPAND_TEST_VAR"), "value");
+        assert_eq!(word("a${EXPAND_TEST_VAR}b"), "avalueb");
+        assert_eq!(word(r#""$EXPAND_TEST_VAR""#), "value");
+        assert_eq!(word("$EXPAND_TEST_UNSET"), "");
+    }
+
+    #[test]
+    fn quoted_and_escaped_variables_are_not_expanded() {
+        assert_eq!(word("'$EXPAND_TEST_VAR'"), "$EXPAND_TEST_VAR");
+        assert_eq!(word(r"\$EXPAND_TEST_VAR"), "$EXPAND_TEST_VAR");
+        assert_eq!(word("$ $"), "$ $");
+    }
+
+    #[test]
+    fn here_documents_are_expanded_without_quote_removal() {
+        let text = "$EXPAND_TEST_VAR '$EXPAND_TEST_VAR' \"a\"\n\\$ \\\\ \\a $((1 + 2))\n";
+        assert_eq!(
+            expand_here_document(text, &variables()).unwrap(),
+            "value 'value' \"a\"\n$ \\ \\a 3\n"
+        );
+    }
+
+    fn fields(word: &str) -> Vec<String> {
+        expand(word, &variables()).unwrap()
+    }
+
+    #[test]
+    fn unquoted_expansions_are_split() {
+        assert_eq!(fields("$EXPAND_TEST_SPLIT"), ["a", "b"]);
+        assert_eq!(fields("x${EXPAND_TEST_SPLIT}y"), ["x", "a", "b", "y"]);
+        assert_eq!(fields(r#""$EXPAND_TEST_SPLIT""#), [" a  b "]);
+    }
+
+    #[test]
+    fn empty_words_are_removed_unless_quoted() {
+        assert_eq!(fields("$EXPAND_TEST_UNSET"), Vec::<String>::new());
+        assert_eq!(fields(r#""$EXPAND_TEST_UNSET""#), [""]);
+        assert_eq!(fields("''"), [""]);
+    }
+
+    #[test]
+    fn braces_are_expanded_to_each_alternative() {
+        assert_eq!(
+            expand_braces("file{1,2,3}.txt"),
+            ["file1.txt", "file2.txt", "file3.txt"]
+        );
+        assert_eq!(expand_braces("{a,}b"), ["ab", "b"]);
+        assert_eq!(expand_braces("{a,b}{1,2}"), ["a1", "a2", "b1", "b2"]);
+        assert_eq!(expand_braces("{a,{b,c}d}"), ["a", "bd", "cd"]);
+    }
+
+    #[test]
+    fn number_sequences_count_up_or_down() {
+        assert_eq!(expand_braces("{1..5}"), ["1", "2", "3", "4", "5"]);
+        assert_eq!(expand_braces("{3..1}"), ["3", "2", "1"]);
+        assert_eq!(expand_braces("{-1..1}"), ["-1", "0", "1"]);
+        assert_eq!(expand_braces("{7..7}"), ["7"]);
+        assert_eq!(expand_braces("x{1..2}y"), ["x1y", "x2y"]);
+    }
+
+    #[test]
+    fn sequences_can_have_a_step() {
+        assert_eq!(expand_braces("{1..10..3}"), ["1", "4", "7", "10"]);
+        assert_eq!(expand_braces("{10..1..4}"), ["10", "6", "2"]);
+        assert_eq!(expand_braces("{1..4..-2}"), ["1", "3"]);
+        assert_eq!(expand_braces("{1..3..0}"), ["1", "2", "3"]);
+    }
+
+    #[test]
+    fn sequences_with_leading_zeros_are_padded() {
+        assert_eq!(expand_braces("{08..10}"), ["08", "09", "10"]);
+        assert_eq!(expand_braces("{1..03}"), ["01", "02", "03"]);
+    }
+
+    #[test]
+    fn letter_sequences_count_up_or_down() {
+        assert_eq!(expand_braces("{a..e}"), ["a", "b", "c", "d", "e"]);
+        assert_eq!(expand_braces("{C..A}"), ["C", "B", "A"]);
+        assert_eq!(expand_braces("{a..e..2}"), ["a", "c", "e"]);
+    }
+
+    #[test]
+    fn malformed_sequences_are_kept() {
+        assert_eq!(expand_braces("{1..b}"), ["{1..b}"]);
+        assert_eq!(expand_braces("{ab..c}"), ["{ab..c}"]);
+        assert_eq!(expand_braces("{1..}"), ["{1..}"]);
+        assert_eq!(expand_braces("{1...3}"), ["{1...3}"]);
+        assert_eq!(expand_braces("{1..3..x}"), ["{1..3..x}"]);
+        assert_eq!(expand_braces("{1..3"), ["{1..3"]);
+    }
+
+    #[test]
+    fn sequences_and_alternatives_can_be_combined() {
+        assert_eq!(expand_braces("{a,b}{1..2}"), ["a1", "a2", "b1", "b2"]);
+        assert_eq!(expand_braces("{x,{1..2}}"), ["x", "1", "2"]);
+    }
+
+    #[test]
+    fn braces_without_alternatives_are_kept() {
+        assert_eq!(expand_braces("{}"), ["{}"]);
+        assert_eq!(expand_braces("{a}"), ["{a}"]);
+        assert_eq!(expand_braces("{a,b"), ["{a,b"]);
+        assert_eq!(expand_braces("${HOME}"), ["${HOME}"]);
+        assert_eq!(expand_braces("{{a,b}"), ["{a", "{b"]);
+    }
+
+    #[test]
+    fn quoted_braces_are_kept() {
+        assert_eq!(expand_braces("'{a,b}'"), ["'{a,b}'"]);
+        assert_eq!(expand_braces(r"\{a,b}"), [r"\{a,b}"]);
+        assert_eq!(expand_braces(r#"{"a,b",c}"#), [r#""a,b""#, "c"]);
+        assert_eq!(fields("x{'a b',c}"), ["xa b", "xc"]);
+        assert_eq!(fields("'a'{b,c}"), ["ab", "ac"]);
+    }
+
+    #[test]
+    fn tilde_is_expanded_at_the_start() {
+        let home = std::env::home_dir().unwrap();
+        let home = home.to_str().unwrap();
+        assert_eq!(word("~"), home);
+        assert_eq!(word("~/src"), format!("{home}/src"));
+        assert_eq!(word("a~"), "a~");
+        assert_eq!(word("'~'"), "~");
+        assert_eq!(word("~user"), "~user");
+    }
+
+    fn pattern(pattern: &str) -> Field {
+        pattern.chars().map(|c| (c, Origin::Unquoted)).collect()
+    }
+
+    #[test]
+    fn glob_patterns_match() {
+        let name = |name: &str| name.chars().collect::<Vec<_>>();
+        assert!(matches_pattern(&pattern("*.rs"), &name("main.rs")));
+        assert!(!matches_pattern(&pattern("*.rs"), &name("main.rso")));
+        assert!(matches_pattern(&pattern("?a*"), &name("bar")));
+        assert!(matches_pattern(&pattern("[a-c]x"), &name("bx")));
+        assert!(!matches_pattern(&pattern("[!a-c]x"), &name("bx")));
+        assert!(matches_pattern(&pattern("[x"), &name("[x")));
+    }
+
+    #[test]
+    fn quoted_glob_characters_are_literal() {
+        let quoted: Field = "*".chars().map(|c| (c, Origin::Quoted)).collect();
+        assert_eq!(expand_pathnames(quoted), ["*"]);
+        assert!(!matches_pattern(&[('*', Origin::Quoted)], &['a']));
+    }
+
+    #[test]
+    fn unmatched_globs_are_kept() {
+        assert_eq!(fields("does-not-exist/*.xyz"), ["does-not-exist/*.xyz"]);
+    }
+
+    #[test]
+    fn globs_are_expanded_in_directories() {
+        // The example is run from the root of the crate.
+        let files = fields("examples/block6/e*.rs");
+        assert_eq!(files, ["examples/block6/expand.rs"]);
+    }
+
+    #[test]
+    fn indexed_variables_are_words_of_the_value() {
+        assert_eq!(word("${EXPAND_TEST_INDEX[1]}"), "1");
+        assert_eq!(word("${EXPAND_TEST_INDEX[3]}x"), "x");
+        assert_eq!(word("${EXPAND_TEST_INDEX}"), "0 1 0");
+    }
+
+    #[test]
+    fn arithmetic_is_expanded() {
+        assert_eq!(word("$((2 + 3 * 4))"), "14");
+        assert_eq!(word("\"$(( (1 + 2) * $EXPAND_TEST_NUMBER ))\""), "9");
+        assert!(expand_word("$((1 / 0))", &variables()).is_err());
+    }
+}
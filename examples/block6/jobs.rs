@@ -0,0 +1,122 @@
+//! Background jobs, started with `&` at the end of a command.
+//!
+//! The shell doesn't wait for background jobs. Instead, it keeps them
+//! in a table, so it can report them once they are done.
+
+use crate::Result;
+use std::process::{Child, Command, ExitStatus};
+
+/// A command running in the background.
+pub struct Job {
+    /// The number of the job, as in `[1]`.
+    pub id: usize,
+    /// The command line of the job, to show it to the user.
+    pub command: String,
+    /// Don't send `SIGHUP` to the job when the shell exits, see `disown -h`.
+    pub nohup: bool,
+    child: Child,
+}
+
+/// The table of all background jobs of the shell.
+#[derive(Default)]
+pub struct Jobs {
+    jobs: Vec<Job>,
+    /// Send `SIGHUP` to all jobs when the shell exits. Off by default, like in bash.
+    pub huponexit: bool,
+}
+
+impl Jobs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start `command` in the background and add it to the table.
+    pub fn spawn(&mut self, command: &mut Command, line: String) -> Result<()> {
+        // Like bash, reuse the numbers of jobs which are done.
+        let id = self.jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+        let child = command.spawn()?;
+        eprintln!("[{id}] {}", child.id());
+        self.jobs.push(Job {
+            id,
+            command: line,
+            nohup: false,
+            child,
+        });
+        Ok(())
+    }
+
+    /// Remove the jobs which are done from the table and report them.
+    ///
+    /// This also cleans up the child processes, which would stay around as
+    /// zombies until we wait for them.
+    pub fn reap(&mut self) {
+        self.jobs.retain_mut(|job| match job.child.try_wait() {
+            Ok(Some(_)) | Err(_) => {
+                eprintln!("[{}]+ Done    {}", job.id, job.command);
+                false
+            }
+            Ok(None) => true,
+        });
+    }
+
+    /// Called when the shell exits. Hangs up all running jobs if `huponexit` is set.
+    pub fn exit(&mut self) {
+        if !self.huponexit {
+            return;
+        }
+        // Don't send signals to jobs which are done, their PID could be reused already.
+        self.reap();
+        for job in self.jobs.iter_mut().filter(|job| !job.nohup) {
+            hang_up(&mut job.child);
+        }
+    }
+
+    /// All jobs in the table, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &Job> {
+        self.jobs.iter()
+    }
+
+    /// Find the job with number `id`, or the most recent job if `id` is `None`.
+    pub fn get_mut(&mut self, id: Option<usize>) -> Option<&mut Job> {
+        match id {
+            Some(id) => self.jobs.iter_mut().find(|job| job.id == id),
+            None => self.jobs.last_mut(),
+        }
+    }
+
+    /// Find the number of the job with the process ID `pid`.
+    pub fn find_pid(&self, pid: u32) -> Option<usize> {
+        let job = self.jobs.iter().find(|job| job.child.id() == pid)?;
+        Some(job.id)
+    }
+
+    /// Wait until the job with number `id` is done and remove it from the table.
+    /// Returns `None` if there is no such job.
+    pub fn wait(&mut self, id: usize) -> Option<Result<ExitStatus>> {
+        let mut job = self.remove(id)?;
+        Some(job.child.wait().map_err(Into::into))
+    }
+
+    /// Remove the job with number `id` from the table, without stopping it.
+    ///
+    /// The shell won't wait for the job anymore, so it stays around as a zombie
+    /// once it is done, until the shell itself exits.
+    pub fn remove(&mut self, id: usize) -> Option<Job> {
+        let index = self.jobs.iter().position(|job| job.id == id)?;
+        Some(self.jobs.remove(index))
+    }
+}
+
+/// Send `SIGHUP` to the process group of the job,
+/// so that processes started by the job receive it as well.
+#[cfg(unix)]
+fn hang_up(child: &mut Child) {
+    // Each job runs in its own process group, which has the PID of the job as its ID.
+    unsafe { libc::kill(-(child.id() as i32), libc::SIGHUP) };
+}
+
+/// There is no `SIGHUP` on Windows, so terminate the job instead.
+#[cfg(not(unix))]
+fn hang_up(child: &mut Child) {
+    let _ = child.kill();
+}
@@ -0,0 +1,714 @@
+//! An interactive line editor.
+//!
+//! When the shell runs in a terminal, it switches the terminal to "raw mode".
+//! In raw mode we get every key press immediately instead of a whole line,
+//! so we can support the arrow keys to edit the line and to go through the history.
+//! Libraries like `rustyline` or `reedline` do the same, with many more features.
+
+use crate::{
+    builtins::clear_sequence,
+    completion::{common_prefix, complete, complete_command, complete_registered, Completions},
+};
+use std::{
+    collections::VecDeque,
+    io::{self, IsTerminal, Read, Write},
+    iter::Peekable,
+};
+
+/// How many lines the editor keeps in memory to navigate with Up and Down.
+const HISTORY_RING_SIZE: usize = 1000;
+
+/// A key press, decoded from the bytes the terminal sends.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Left,
+    Right,
+    Up,
+    Down,
+    Tab,
+    /// Ctrl-C
+    Interrupt,
+    /// Ctrl-D
+    EndOfFile,
+    /// Ctrl-L
+    ClearScreen,
+    /// Ctrl-R
+    ReverseSearch,
+    /// Ctrl-G
+    Cancel,
+    /// Esc on its own, not as the start of an escape sequence.
+    Escape,
+    /// A key we don't support.
+    Unknown,
+}
+
+/// Decode the next key from the bytes the terminal sends.
+/// Returns `None` if there are no more bytes.
+fn read_key(bytes: &mut Peekable<impl Iterator<Item = u8>>) -> Option<Key> {
+    let key = match bytes.next()? {
+        b'\r' | b'\n' => Key::Enter,
+        // Terminals send DEL (127) for the backspace key, some send Ctrl-H (8).
+        0x7f | 0x08 => Key::Backspace,
+        b'\t' => Key::Tab,
+        0x03 => Key::Interrupt,
+        0x04 => Key::EndOfFile,
+        0x0c => Key::ClearScreen,
+        0x12 => Key::ReverseSearch,
+        0x07 => Key::Cancel,
+        // Arrow keys are sent as escape sequences like `ESC [ A`.
+        // A lone Esc is only recognized when the next key arrives, which is kept.
+        0x1b => match bytes.next_if_eq(&b'[') {
+            None => Key::Escape,
+            Some(_) => match bytes.next() {
+                Some(b'A') => Key::Up,
+                Some(b'B') => Key::Down,
+                Some(b'C') => Key::Right,
+                Some(b'D') => Key::Left,
+                _ => Key::Unknown,
+            },
+        },
+        byte if byte.is_ascii_control() => Key::Unknown,
+        byte => {
+            // Characters outside of ASCII are encoded in multiple bytes.
+            let len = match byte.leading_ones() {
+                2 => 2,
+                3 => 3,
+                4 => 4,
+                _ => 1,
+            };
+            let mut encoded = vec![byte];
+            encoded.extend(bytes.take(len - 1));
+            match std::str::from_utf8(&encoded)
+                .ok()
+                .and_then(|s| s.chars().next())
+            {
+                Some(c) => Key::Char(c),
+                None => Key::Unknown,
+            }
+        }
+    };
+    Some(key)
+}
+
+/// The line being edited and the position of the cursor in it.
+#[derive(Default, Clone, PartialEq, Debug)]
+struct Buffer {
+    chars: Vec<char>,
+    /// Index in `chars` in front of which the next character is inserted.
+    cursor: usize,
+}
+
+impl Buffer {
+    fn insert(&mut self, c: char) {
+        self.chars.insert(self.cursor, c);
+        self.cursor += 1;
+    }
+
+    /// Delete the character in front of the cursor.
+    fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.chars.remove(self.cursor);
+        }
+    }
+
+    fn left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    fn right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.chars.len());
+    }
+
+    /// Replace the content of the buffer and move the cursor to the end.
+    fn set(&mut self, line: &str) {
+        self.chars = line.chars().collect();
+        self.cursor = self.chars.len();
+    }
+
+    fn line(&self) -> String {
+        self.chars.iter().collect()
+    }
+
+    /// Whether Ctrl-D ends the input. Like in bash, this is only the case on an empty line,
+    /// so a half-typed command isn't lost by accident.
+    fn ends_input(&self) -> bool {
+        self.chars.is_empty()
+    }
+
+    /// Index of the first character of the word in front of the cursor.
+    fn word_start(&self) -> usize {
+        self.chars[..self.cursor]
+            .iter()
+            .rposition(|c| c.is_whitespace() || is_command_separator(*c))
+            .map_or(0, |i| i + 1)
+    }
+
+    /// Whether the word in front of the cursor is the name of a command.
+    fn is_command_position(&self) -> bool {
+        let before = &self.chars[..self.word_start()];
+        match before.iter().rev().find(|c| !c.is_whitespace()) {
+            Some(c) => is_command_separator(*c),
+            None => true,
+        }
+    }
+
+    /// The command whose first argument is the word in front of the cursor, if it is one.
+    fn command_of_first_argument(&self) -> Option<String> {
+        let before = &self.chars[..self.word_start()];
+        let command_start = before
+            .iter()
+            .rposition(|c| is_command_separator(*c))
+            .map_or(0, |i| i + 1);
+        let words: String = before[command_start..].iter().collect();
+        match words.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [command] => Some(command.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Complete the word in front of the cursor.
+    ///
+    /// If there is exactly one candidate, the word is replaced by it.
+    /// Otherwise, the word is extended as far as all candidates agree.
+    /// The candidates are returned, so they can be shown to the user.
+    fn complete(&mut self, completions: &Completions) -> Vec<String> {
+        let start = self.word_start();
+        let word: String = self.chars[start..self.cursor].iter().collect();
+        let registered = self
+            .command_of_first_argument()
+            .and_then(|command| complete_registered(completions, &command, &word));
+        let candidates = if self.is_command_position() {
+            complete_command(&word)
+        } else if let Some(registered) = registered {
+            registered
+        } else {
+            complete(&word)
+        };
+
+        let mut completion = common_prefix(&candidates).to_string();
+        // Add a space after a complete file name, but keep typing into directories.
+        if candidates.len() == 1 && !completion.ends_with('/') {
+            completion.push(' ');
+        }
+        if completion.len() > word.len() {
+            self.chars.splice(start..self.cursor, completion.chars());
+            self.cursor = start + completion.chars().count();
+        }
+        candidates
+    }
+}
+
+/// Characters after which a new command starts, e.g. `|` in `ls | wc`.
+fn is_command_separator(c: char) -> bool {
+    matches!(c, '|' | ';' | '&')
+}
+
+/// The escape sequences which redraw the prompt and the buffer on the current line.
+fn redraw(prompt: &str, buffer: &Buffer) -> String {
+    // `\r` moves to the start of the line, `ESC [ K` clears the rest of it.
+    let mut sequence = format!("\r{prompt}{}\x1b[K", buffer.line());
+    let chars_after_cursor = buffer.chars.len() - buffer.cursor;
+    if chars_after_cursor > 0 {
+        // Move the cursor back to where it was.
+        sequence.push_str(&format!("\x1b[{chars_after_cursor}D"));
+    }
+    sequence
+}
+
+/// The escape sequences which clear the screen and draw the prompt and the buffer
+/// at the top, for Ctrl-L. The buffer is kept, so the user can continue typing.
+fn clear_and_redraw(prompt: &str, buffer: &Buffer) -> String {
+    let clear = String::from_utf8_lossy(clear_sequence(true));
+    format!("{clear}{}", redraw(prompt, buffer))
+}
+
+/// The most recent entry of `history` (oldest first) which contains `query`, for Ctrl-R.
+/// An empty query matches nothing.
+fn reverse_search<'a>(
+    history: impl DoubleEndedIterator<Item = &'a String>,
+    query: &str,
+) -> Option<&'a str> {
+    if query.is_empty() {
+        return None;
+    }
+    history
+        .rev()
+        .find(|entry| entry.contains(query))
+        .map(String::as_str)
+}
+
+/// The state of a reverse search, while the user types the query after Ctrl-R.
+struct Search {
+    query: String,
+    /// The line from before the search, which is restored if the search is cancelled.
+    original: Buffer,
+    /// Whether the query doesn't match any entry.
+    failed: bool,
+}
+
+impl Search {
+    fn new(original: Buffer) -> Self {
+        Self {
+            query: String::new(),
+            original,
+            failed: false,
+        }
+    }
+
+    /// Shown instead of the usual prompt, like in bash.
+    fn prompt(&self) -> String {
+        let failed = if self.failed { "failed " } else { "" };
+        format!("({failed}reverse-i-search)`{}': ", self.query)
+    }
+}
+
+/// The previous commands, which can be recalled with Up and Down.
+#[derive(Debug)]
+struct HistoryRing {
+    entries: VecDeque<String>,
+    /// The entry which is currently shown.
+    /// Equal to the number of entries, if the user isn't navigating the history.
+    position: usize,
+    /// The line the user typed before navigating the history.
+    draft: String,
+}
+
+impl HistoryRing {
+    fn new(entries: impl IntoIterator<Item = String>) -> Self {
+        let mut ring = Self {
+            entries: VecDeque::new(),
+            position: 0,
+            draft: String::new(),
+        };
+        for entry in entries {
+            ring.push(entry);
+        }
+        ring
+    }
+
+    /// Add a line and stop navigating.
+    fn push(&mut self, entry: String) {
+        if self.entries.len() == HISTORY_RING_SIZE {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+        self.position = self.entries.len();
+    }
+
+    /// Go to the previous (older) entry.
+    /// `current` is the line in the editor, which is restored by going past the newest entry.
+    fn previous(&mut self, current: &str) -> Option<&str> {
+        if self.position == 0 {
+            return None;
+        }
+        if self.position == self.entries.len() {
+            self.draft = current.to_string();
+        }
+        self.position -= 1;
+        Some(&self.entries[self.position])
+    }
+
+    /// Go to the next (newer) entry.
+    fn next(&mut self) -> Option<&str> {
+        if self.position >= self.entries.len() {
+            return None;
+        }
+        self.position += 1;
+        match self.entries.get(self.position) {
+            Some(entry) => Some(entry),
+            None => Some(&self.draft),
+        }
+    }
+}
+
+/// Read lines from a terminal with support for editing and history.
+pub struct LineEditor {
+    history: HistoryRing,
+}
+
+impl LineEditor {
+    /// Create a new editor. `history` contains the previous commands, oldest first.
+    pub fn new(history: Vec<String>) -> Self {
+        Self {
+            history: HistoryRing::new(history),
+        }
+    }
+
+    /// Whether the editor can be used, i.e. the shell is attached to a terminal.
+    /// Otherwise, e.g. if stdin is a pipe, lines should be read with `read_line`.
+    pub fn is_available() -> bool {
+        cfg!(unix) && io::stdin().is_terminal() && io::stdout().is_terminal()
+    }
+
+    /// Add a line to the history, so it can be recalled with Up.
+    pub fn add_history(&mut self, line: &str) {
+        self.history.push(line.to_string());
+    }
+
+    /// Show `prompt` and read a line. Returns `None` on Ctrl-D on an empty line.
+    ///
+    /// Tab uses `completions` for the first argument of the commands registered in it.
+    pub fn read_line(
+        &mut self,
+        prompt: &str,
+        completions: &Completions,
+    ) -> io::Result<Option<String>> {
+        let _raw_mode = RawMode::enable()?;
+        let mut stdout = io::stdout();
+        let mut bytes = io::stdin()
+            .lock()
+            .bytes()
+            .map_while(|byte| byte.ok())
+            .peekable();
+        let mut buffer = Buffer::default();
+        let mut search: Option<Search> = None;
+
+        write!(stdout, "{}", redraw(prompt, &buffer))?;
+        stdout.flush()?;
+        while let Some(key) = read_key(&mut bytes) {
+            if let Some(mut active) = search.take() {
+                let searching = match key {
+                    Key::Char(c) => {
+                        active.query.push(c);
+                        true
+                    }
+                    Key::Backspace => {
+                        active.query.pop();
+                        true
+                    }
+                    Key::Escape | Key::Cancel => {
+                        // Give up and go back to the line from before the search.
+                        buffer = active.original;
+                        write!(stdout, "{}", redraw(prompt, &buffer))?;
+                        stdout.flush()?;
+                        continue;
+                    }
+                    Key::ReverseSearch | Key::EndOfFile | Key::ClearScreen | Key::Unknown => true,
+                    // Any other key accepts the match and is handled as usual, so Enter runs it.
+                    _ => false,
+                };
+                if searching {
+                    match reverse_search(self.history.entries.iter(), &active.query) {
+                        Some(entry) => {
+                            buffer.set(entry);
+                            active.failed = false;
+                        }
+                        // Keep showing the last match, like bash.
+                        None => active.failed = !active.query.is_empty(),
+                    }
+                    write!(stdout, "{}", redraw(&active.prompt(), &buffer))?;
+                    stdout.flush()?;
+                    search = Some(active);
+                    continue;
+                }
+            }
+            match key {
+                Key::Char(c) => buffer.insert(c),
+                Key::Backspace => buffer.backspace(),
+                Key::Left => buffer.left(),
+                Key::Right => buffer.right(),
+                Key::Up => {
+                    if let Some(entry) = self.history.previous(&buffer.line()) {
+                        buffer.set(entry);
+                    }
+                }
+                Key::Down => {
+                    if let Some(entry) = self.history.next() {
+                        buffer.set(entry);
+                    }
+                }
+                Key::Tab => {
+                    let candidates = buffer.complete(completions);
+                    // If there is more than one candidate, list them below the line.
+                    if candidates.len() > 1 {
+                        writeln!(stdout)?;
+                        write!(stdout, "{}", candidates.join("  "))?;
+                        writeln!(stdout)?;
+                    }
+                }
+                Key::Enter => {
+                    writeln!(stdout)?;
+                    // Start from the newest entry next time.
+                    self.history.position = self.history.entries.len();
+                    return Ok(Some(buffer.line()));
+                }
+                Key::Interrupt => {
+                    // Discard the line and start over with a fresh prompt.
+                    writeln!(stdout, "^C")?;
+                    buffer = Buffer::default();
+                    self.history.position = self.history.entries.len();
+                }
+                Key::EndOfFile if buffer.ends_input() => {
+                    writeln!(stdout)?;
+                    return Ok(None);
+                }
+                Key::EndOfFile => continue,
+                Key::ClearScreen => {
+                    write!(stdout, "{}", clear_and_redraw(prompt, &buffer))?;
+                    stdout.flush()?;
+                    continue;
+                }
+                Key::ReverseSearch => {
+                    let active = Search::new(buffer.clone());
+                    write!(stdout, "{}", redraw(&active.prompt(), &buffer))?;
+                    stdout.flush()?;
+                    search = Some(active);
+                    continue;
+                }
+                Key::Cancel | Key::Escape | Key::Unknown => continue,
+            }
+            write!(stdout, "{}", redraw(prompt, &buffer))?;
+            stdout.flush()?;
+        }
+        Ok(None)
+    }
+}
+
+/// Puts the terminal into raw mode while it is alive.
+/// The previous settings are restored when it is dropped.
+#[cfg(unix)]
+struct RawMode {
+    original: libc::termios,
+}
+
+#[cfg(unix)]
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        // SAFETY: `termios` is a plain C struct, so it can be zeroed.
+        // `tcgetattr` initializes it.
+        let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut termios) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let original = termios;
+        // Don't wait for a whole line (`ICANON`) and don't echo the keys (`ECHO`).
+        // We print the line ourselves.
+        // Ctrl-C is read as a key instead of sending `SIGINT` (`ISIG`).
+        termios.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+        // Return from `read` as soon as a single byte is available.
+        termios.c_cc[libc::VMIN] = 1;
+        termios.c_cc[libc::VTIME] = 0;
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { original })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original) };
+    }
+}
+
+/// Raw mode is only implemented for Unix. See [`LineEditor::is_available`].
+#[cfg(not(unix))]
+struct RawMode;
+
+#[cfg(not(unix))]
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "raw mode is not supported on this platform",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ring(entries: &[&str]) -> HistoryRing {
+        HistoryRing::new(entries.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn up_goes_to_older_entries() {
+        let mut history = ring(&["ls", "pwd"]);
+        assert_eq!(history.previous(""), Some("pwd"));
+        assert_eq!(history.previous(""), Some("ls"));
+        // There is nothing older than the first entry.
+        assert_eq!(history.previous(""), None);
+    }
+
+    #[test]
+    fn down_restores_the_draft() {
+        let mut history = ring(&["ls", "pwd"]);
+        assert_eq!(history.next(), None);
+        history.previous("ech");
+        history.previous("ignored");
+        assert_eq!(history.next(), Some("pwd"));
+        assert_eq!(history.next(), Some("ech"));
+        assert_eq!(history.next(), None);
+    }
+
+    #[test]
+    fn push_resets_the_position() {
+        let mut history = ring(&["ls"]);
+        history.previous("");
+        history.push("pwd".to_string());
+        assert_eq!(history.previous(""), Some("pwd"));
+    }
+
+    #[test]
+    fn ring_drops_oldest_entries() {
+        let mut history = ring(&[]);
+        for i in 0..=HISTORY_RING_SIZE {
+            history.push(i.to_string());
+        }
+        assert_eq!(history.entries.len(), HISTORY_RING_SIZE);
+        assert_eq!(history.entries.front().unwrap(), "1");
+    }
+
+    #[test]
+    fn keys_are_decoded() {
+        let mut bytes = b"a\x1b[A\x1b[D\x7f\x03\x0c\x12\x1bb\r"
+            .iter()
+            .copied()
+            .peekable();
+        let keys: Vec<Key> = std::iter::from_fn(|| read_key(&mut bytes)).collect();
+        assert_eq!(
+            keys,
+            vec![
+                Key::Char('a'),
+                Key::Up,
+                Key::Left,
+                Key::Backspace,
+                Key::Interrupt,
+                Key::ClearScreen,
+                Key::ReverseSearch,
+                Key::Escape,
+                Key::Char('b'),
+                Key::Enter
+            ]
+        );
+    }
+
+    #[test]
+    fn multi_byte_chars_are_decoded() {
+        let mut bytes = "ü🦀".bytes().peekable();
+        assert_eq!(read_key(&mut bytes), Some(Key::Char('ü')));
+        assert_eq!(read_key(&mut bytes), Some(Key::Char('🦀')));
+    }
+
+    #[test]
+    fn reverse_search_finds_the_most_recent_match() {
+        let history = ring(&["git status", "ls", "git commit", "pwd"]);
+        assert_eq!(
+            reverse_search(history.entries.iter(), "git"),
+            Some("git commit")
+        );
+        assert_eq!(
+            reverse_search(history.entries.iter(), "stat"),
+            Some("git status")
+        );
+        assert_eq!(reverse_search(history.entries.iter(), "cargo"), None);
+    }
+
+    #[test]
+    fn reverse_search_with_empty_query_finds_nothing() {
+        let history = ring(&["ls"]);
+        assert_eq!(reverse_search(history.entries.iter(), ""), None);
+    }
+
+    #[test]
+    fn search_prompt_shows_the_query() {
+        let mut search = Search::new(Buffer::default());
+        search.query.push_str("gi");
+        assert_eq!(search.prompt(), "(reverse-i-search)`gi': ");
+        search.failed = true;
+        assert_eq!(search.prompt(), "(failed reverse-i-search)`gi': ");
+    }
+
+    #[test]
+    fn buffer_edits_at_cursor() {
+        let mut buffer = Buffer::default();
+        buffer.set("ac");
+        buffer.left();
+        buffer.insert('b');
+        assert_eq!(buffer.line(), "abc");
+        buffer.right();
+        buffer.right();
+        buffer.backspace();
+        assert_eq!(buffer.line(), "ab");
+    }
+
+    #[test]
+    fn end_of_file_only_ends_empty_lines() {
+        let mut buffer = Buffer::default();
+        assert!(buffer.ends_input());
+        buffer.set("ls");
+        assert!(!buffer.ends_input());
+        buffer.backspace();
+        buffer.backspace();
+        assert!(buffer.ends_input());
+    }
+
+    #[test]
+    fn command_position_is_detected() {
+        let mut buffer = Buffer::default();
+        buffer.set("ec");
+        assert!(buffer.is_command_position());
+        buffer.set("ls | w");
+        assert!(buffer.is_command_position());
+        buffer.set("ls;w");
+        assert_eq!(buffer.word_start(), 3);
+        assert!(buffer.is_command_position());
+        buffer.set("ls src");
+        assert!(!buffer.is_command_position());
+        buffer.set("ls ");
+        assert!(!buffer.is_command_position());
+    }
+
+    #[test]
+    fn single_candidate_is_inserted() {
+        let dir = std::env::temp_dir().join(format!("line_editor_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("only_dir")).unwrap();
+        let mut buffer = Buffer::default();
+        buffer.set(&format!("cd {}/only_d", dir.display()));
+        assert_eq!(buffer.complete(&Completions::new()).len(), 1);
+        assert_eq!(buffer.line(), format!("cd {}/only_dir/", dir.display()));
+        assert_eq!(buffer.cursor, buffer.chars.len());
+    }
+
+    #[test]
+    fn registered_words_complete_the_first_argument() {
+        let mut completions = Completions::new();
+        let words = ["build", "bench"].map(String::from).to_vec();
+        completions.insert("cargo".to_string(), words);
+        let mut buffer = Buffer::default();
+        buffer.set("ls | cargo bu");
+        assert_eq!(buffer.complete(&completions), ["build"]);
+        assert_eq!(buffer.line(), "ls | cargo build ");
+        // Only the first argument is completed from the registered words.
+        buffer.set("cargo build bu");
+        assert_eq!(buffer.command_of_first_argument(), None);
+    }
+
+    #[test]
+    fn redraw_moves_cursor_back() {
+        let mut buffer = Buffer::default();
+        buffer.set("echo");
+        assert_eq!(redraw("> ", &buffer), "\r> echo\x1b[K");
+        buffer.left();
+        buffer.left();
+        assert_eq!(redraw("> ", &buffer), "\r> echo\x1b[K\x1b[2D");
+    }
+
+    #[test]
+    fn clear_screen_keeps_the_buffer() {
+        let mut buffer = Buffer::default();
+        buffer.set("ls -l");
+        buffer.left();
+        let sequence = clear_and_redraw("$ ", &buffer);
+        assert!(sequence.starts_with("\x1b[2J\x1b[H"), "{sequence:?}");
+        assert!(sequence.ends_with("\r$ ls -l\x1b[K\x1b[1D"), "{sequence:?}");
+        assert_eq!(buffer.line(), "ls -l");
+    }
+}
@@ -0,0 +1,928 @@
+use std::{
+    ffi::OsStr,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, PipeReader, PipeWriter, Read, Write},
+    path::Path,
+    process::{Child, ChildStdout, Command, ExitStatus, Output, Stdio},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+mod arithmetic;
+mod builtins;
+mod completion;
+mod expand;
+mod jobs;
+mod line_editor;
+mod parser;
+mod prompt;
+mod shell;
+mod signals;
+mod variables;
+
+use completion::Completions;
+use jobs::Jobs;
+use line_editor::LineEditor;
+use parser::{
+    read_continued_lines, read_here_documents, Chain, Cmd, Element, Pipeline, Redirect,
+    RedirectKind,
+};
+use prompt::{render_prompt, show_prompt};
+use shell::Shell;
+
+/// Alias for our `Result` type. You could also use `anyhow` instead.
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// The prompt for the following lines of a command, like the lines of a here-document.
+const CONTINUATION_PROMPT: &str = "> ";
+
+/// The longest line the shell reads from a pipe, in bytes.
+/// Longer lines are rejected, so a misbehaving program can't make the shell run out of memory.
+const MAX_LINE_LENGTH: usize = 1024 * 1024;
+
+/// Options which start the shell as a login shell.
+const LOGIN_FLAGS: &[&str] = &["--login", "-l"];
+
+/// Options which print the version of the shell.
+const VERSION_FLAGS: &[&str] = &["--version", "-V"];
+
+/// Options which print the parsed commands instead of running them.
+const PARSE_ONLY_FLAGS: &[&str] = &["--parse-only", "-n"];
+
+/// Option which starts the shell without running the startup file.
+const NO_RC_FLAG: &str = "--no-rc";
+
+/// Builtins which read their input until it ends.
+/// In the middle of a pipeline, they run on their own thread, see [`Cmd::spawn_filter`].
+const FILTERS: &[&str] = &["sort", "head", "tail", "tr"];
+
+fn main() {
+    signals::install_handlers();
+    shell::increment_shell_level();
+    let mut shell = Shell::new();
+
+    let mut args: Vec<_> = std::env::args_os().skip(1).collect();
+    if args
+        .first()
+        .is_some_and(|arg| VERSION_FLAGS.iter().any(|flag| arg == flag))
+    {
+        println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+        return;
+    }
+    let mut run_rc_file = true;
+    // Options of the shell come first. Everything after the script belongs to the script.
+    // `--login` is skipped here, `is_login_shell` checks it when it's needed.
+    while let Some(flag) = args.first().filter(|flag| is_shell_option(flag)) {
+        if flag == "-e" {
+            // `rush -e script.sh` stops at the first command which fails, e.g. in CI.
+            shell.options.errexit = true;
+        } else if PARSE_ONLY_FLAGS.iter().any(|parse_only| flag == parse_only) {
+            // `rush -n script.sh` shows how the script is parsed, e.g. to debug the parser.
+            shell.options.parse_only = true;
+        } else if flag == NO_RC_FLAG {
+            // `rush --no-rc` starts without the user's aliases, e.g. to debug the startup file.
+            run_rc_file = false;
+        }
+        args.remove(0);
+    }
+    // `rush -c 'echo hi'` runs a single line, e.g. for command substitution.
+    if let [flag, command, ..] = args.as_slice() {
+        if flag == "-c" {
+            let status = shell.run_line(&command.to_string_lossy());
+            shell.jobs.exit();
+            // Commands killed by a signal don't have an exit code.
+            std::process::exit(status.map_or(0, |status| status.code().unwrap_or(1)));
+        }
+    }
+    // `rush script.sh`, or a script starting with `#!/path/to/rush`.
+    // The kernel runs the shell with the path of the script as an argument.
+    if let Some(script) = args.first() {
+        let status = shell.run_script(Path::new(script));
+        shell.jobs.exit();
+        std::process::exit(status);
+    }
+
+    let history = builtins::History::new();
+    let mut editor = LineEditor::new(history.entries().unwrap_or_default());
+    if run_rc_file {
+        shell.run_rc_file();
+    }
+    // Stop once stdin is closed, e.g. on Ctrl-D.
+    while let Some(line) = read_line(&mut editor, &shell) {
+        shell.jobs.reap();
+        // The whole command is added to the history, not only its first line.
+        let line = read_continued_lines(&line, || {
+            read_continuation_line(&mut editor, &shell.completions)
+        });
+        let line = match history.expand(line.trim()) {
+            Ok(expansion) => {
+                if expansion.substituted {
+                    // Show the user what is going to run.
+                    eprintln!("{}", expansion.line);
+                }
+                expansion.line
+            }
+            Err(e) => {
+                eprintln!("Error: {e}");
+                continue;
+            }
+        };
+        // The command still runs if the history can't be written, e.g. on a full disk.
+        if let Err(e) = history.add(&line) {
+            eprintln!("Error: {e}");
+        }
+        editor.add_history(&line);
+        let line = read_here_documents(&line, || {
+            read_continuation_line(&mut editor, &shell.completions)
+        });
+        shell.run_line(&line);
+    }
+    shell.jobs.exit();
+    // Like `exit` without an argument, use the status of the last command.
+    std::process::exit(shell.last_status.unwrap_or(0));
+}
+
+/// Whether `arg` is one of the options of the shell, which come before a script or `-c`.
+fn is_shell_option(arg: &OsStr) -> bool {
+    let flags = [LOGIN_FLAGS, PARSE_ONLY_FLAGS, &["-e", NO_RC_FLAG]];
+    flags.concat().iter().any(|flag| arg == *flag)
+}
+
+/// Whether the shell is a login shell, i.e. the shell started when the user logs in.
+///
+/// Like in other shells, this is the case if it was started with `--login`,
+/// or if the name it was started with begins with `-`, like `-rush`.
+/// A `--login` after the script, like in `rush script.sh --login`, is an argument of the script.
+fn is_login_shell() -> bool {
+    let mut args = std::env::args_os();
+    let name_starts_with_dash = args
+        .next()
+        .is_some_and(|name| name.as_encoded_bytes().starts_with(b"-"));
+    let mut options = args.take_while(|arg| is_shell_option(arg));
+    name_starts_with_dash || options.any(|arg| LOGIN_FLAGS.iter().any(|flag| arg == *flag))
+}
+
+/// Read a line from stdin. Returns `None` on end of file.
+///
+/// In a terminal, the line editor is used. Otherwise, e.g. if the input
+/// is piped into the shell, the line is read as it is.
+/// If reading fails, the error is reported and treated like the end of the input.
+fn read_line(editor: &mut LineEditor, shell: &Shell) -> Option<String> {
+    let ps1 = shell.variables.get("PS1");
+    if LineEditor::is_available() {
+        let prompt = render_prompt(ps1.as_deref(), shell.last_status);
+        return editor
+            .read_line(&prompt, &shell.completions)
+            .unwrap_or_else(|e| {
+                eprintln!("Error: {e}");
+                None
+            });
+    }
+
+    show_prompt(ps1.as_deref(), shell.last_status);
+    read_stdin_line()
+}
+
+/// Read the next line of a command which spans several lines, like a here-document.
+fn read_continuation_line(editor: &mut LineEditor, completions: &Completions) -> Option<String> {
+    if LineEditor::is_available() {
+        return editor
+            .read_line(CONTINUATION_PROMPT, completions)
+            .unwrap_or_else(|e| {
+                eprintln!("Error: {e}");
+                None
+            });
+    }
+    read_stdin_line()
+}
+
+/// Read a line from stdin without the line editor. Returns `None` on end of file.
+///
+/// Lines longer than [`MAX_LINE_LENGTH`] are skipped with an error.
+/// Other errors are reported, and then treated like the end of the input.
+fn read_stdin_line() -> Option<String> {
+    let mut stdin = io::stdin().lock();
+    loop {
+        match read_limited_line(&mut stdin, MAX_LINE_LENGTH) {
+            Ok(line) => return line,
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => eprintln!("Error: {e}"),
+            Err(e) => {
+                eprintln!("Error: {e}");
+                return None;
+            }
+        }
+    }
+}
+
+/// Read a line like [`BufRead::read_line`], but keep at most `max_length` bytes in memory.
+///
+/// A longer line is an error, and the rest of it is skipped, so the next line can be read.
+fn read_limited_line(reader: &mut impl BufRead, max_length: usize) -> io::Result<Option<String>> {
+    let mut bytes = Vec::new();
+    // One more byte than allowed, to tell a line of exactly `max_length` bytes from a longer one.
+    let limit = max_length as u64 + 1;
+    reader.by_ref().take(limit).read_until(b'\n', &mut bytes)?;
+    if bytes.is_empty() {
+        return Ok(None);
+    }
+    let content_length = bytes.len() - usize::from(bytes.ends_with(b"\n"));
+    if content_length > max_length {
+        if !bytes.ends_with(b"\n") {
+            reader.skip_until(b'\n')?;
+        }
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("line too long, the limit is {max_length} bytes"),
+        ));
+    }
+    String::from_utf8(bytes)
+        .map(Some)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "line is not valid UTF-8"))
+}
+
+impl Chain {
+    /// Run the chain, print its output and return the exit status of the last pipeline.
+    fn run(self, shell: &mut Shell) -> Option<ExitStatus> {
+        if self.background {
+            return Some(self.run_background(shell).status);
+        }
+        let mut status = None;
+        // Skip the next pipeline, because of the result of the one before `&&` or `||`.
+        let mut skip = false;
+        for e in self.elements {
+            match e {
+                Element::Pipeline(_) if skip => skip = false,
+                Element::Pipeline(mut pipeline) => {
+                    let start = pipeline.take_time().then(Instant::now);
+                    let output = pipeline.run(shell);
+                    print_output(&output.stdout);
+                    if let Some(start) = start {
+                        eprintln!("\nreal\t{}", format_elapsed(start.elapsed()));
+                    }
+                    status = Some(output.status);
+                    // Update `$?` right away, for `false || exit`.
+                    shell.last_status = output.status.code();
+                }
+                // Without a pipeline before the operator, there is nothing to check.
+                Element::And => skip = !status?.success(),
+                Element::Or => skip = status?.success(),
+            }
+        }
+        status
+    }
+
+    /// Start the chain as a background job, without waiting for it.
+    fn run_background(self, shell: &mut Shell) -> Output {
+        let result = match &self.elements[..] {
+            [Element::Pipeline(Pipeline { cmds })] if cmds.len() == 1 => {
+                cmds[0].expand(&shell.variables).and_then(|cmd| {
+                    cmd.trace(shell);
+                    cmd.spawn_background(&mut shell.jobs)
+                })
+            }
+            _ => Err("only a single command can run in the background".into()),
+        };
+        match result {
+            // Starting the job succeeded, the job itself may still fail later.
+            Ok(()) => builtins::output(0, Vec::new()),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                builtins::output(1, Vec::new())
+            }
+        }
+    }
+}
+
+impl Pipeline {
+    /// Remove `time` in front of the pipeline and return whether it was there.
+    ///
+    /// Like in bash, `time` is not a command but a keyword which measures the whole
+    /// pipeline. So `time ls | wc -l` also includes the time of `wc`.
+    fn take_time(&mut self) -> bool {
+        let Some(first) = self.cmds.first_mut().filter(|cmd| cmd.binary == "time") else {
+            return false;
+        };
+        let mut words = std::mem::take(&mut first.args).into_iter();
+        first.binary = words.next().unwrap_or_default();
+        first.args = words.collect();
+        true
+    }
+
+    /// Run the commands of the pipeline, connecting the stdout of each to the stdin of the next.
+    ///
+    /// External commands run at the same time, connected by pipes of the operating
+    /// system. This way, the data streams through the pipeline and `yes | head` stops
+    /// once `head` is done. Builtins which read their input, like `tr`, run on their own thread
+    /// when they are not the last command, see [`Cmd::spawn_filter`].
+    /// All other builtins run in the shell, one after the other.
+    ///
+    /// The last command writes to the stdout of the shell directly, if it is an external one.
+    /// Otherwise, its output is returned, with the exit status of the pipeline
+    /// or with `set -o pipefail`, the status of the last command which failed.
+    fn run(&self, shell: &mut Shell) -> Output {
+        let mut input = None;
+        // The status of each command, `None` while it is still running.
+        let mut statuses = Vec::with_capacity(self.cmds.len());
+        let mut children = Vec::new();
+        let mut threads = Vec::new();
+        // Forward Ctrl-C to the last command which was started, until the pipeline is done.
+        // The commands before it stop once they can't write to the pipe anymore.
+        let mut foreground = None;
+        for (i, cmd) in self.cmds.iter().enumerate() {
+            let last = i == self.cmds.len() - 1;
+            let stage = match cmd.expand(&shell.variables) {
+                Ok(cmd) if !last && cmd.is_filter() => cmd.spawn_filter(input.take(), shell),
+                Ok(cmd) => cmd.run(input.take(), !last, shell),
+                // The command doesn't run if its expansion fails, e.g. on division by zero.
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    Stage::Done(builtins::output(1, Vec::new()))
+                }
+            };
+            match stage {
+                Stage::Done(output) => {
+                    statuses.push(Some(output.status));
+                    input = Some(PipeInput::Output(output));
+                }
+                Stage::Running(mut child) => {
+                    // Only one command can be in the foreground at a time.
+                    drop(foreground.take());
+                    foreground = Some(signals::Foreground::new(child.id()));
+                    input = child.stdout.take().map(PipeInput::Child);
+                    children.push((statuses.len(), child));
+                    statuses.push(None);
+                }
+                Stage::Streaming(thread, stdout) => {
+                    input = Some(PipeInput::Pipe(stdout));
+                    threads.push((statuses.len(), thread));
+                    statuses.push(None);
+                }
+            }
+        }
+
+        let stdout = match input {
+            Some(PipeInput::Output(output)) => output.stdout,
+            // The last command may still be running, read its output until it exits.
+            Some(PipeInput::Child(stdout)) => read_all(stdout),
+            Some(PipeInput::Pipe(stdout)) => read_all(stdout),
+            None => Vec::new(),
+        };
+        // Wait for all commands, otherwise they would stay around as zombies.
+        for (i, mut child) in children {
+            statuses[i] = Some(wait(&mut child));
+        }
+        for (i, thread) in threads {
+            let status = thread.join();
+            statuses[i] = Some(status.unwrap_or_else(|_| builtins::output(1, Vec::new()).status));
+        }
+        let statuses: Vec<ExitStatus> = statuses.into_iter().flatten().collect();
+
+        // Like in bash, `${PIPESTATUS[1]}` is the exit code of the second command.
+        let codes: Vec<String> = statuses
+            .iter()
+            .map(|status| status.code().unwrap_or(1).to_string())
+            .collect();
+        // It is a variable of the shell, so it isn't passed to commands.
+        shell.variables.set("PIPESTATUS", &codes.join(" "));
+
+        let failed = statuses.iter().rev().find(|status| !status.success());
+        let status = match failed {
+            Some(failed) if shell.options.pipefail => *failed,
+            // An empty pipeline, like a lone `|`, succeeds.
+            _ => statuses.last().copied().unwrap_or_default(),
+        };
+        Output {
+            status,
+            stdout,
+            stderr: Vec::new(),
+        }
+    }
+}
+
+/// Quote `word` for the output of `set -x`, if it contains spaces or other special characters.
+/// This shows where a word starts and ends, e.g. in `+ echo 'a b'`.
+fn quote_for_trace(word: &str) -> String {
+    let plain = word
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-_./=:,@%+".contains(c));
+    if plain && !word.is_empty() {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', r"'\''"))
+    }
+}
+
+/// Format a duration like bash does for `time`, e.g. `1m2.345s`.
+fn format_elapsed(elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs_f64();
+    format!("{}m{:.3}s", elapsed.as_secs() / 60, seconds % 60.0)
+}
+
+/// Print the output of a builtin to the stdout of the shell.
+///
+/// Writing fails if nobody reads the output anymore, e.g. in `rush -c 'help' | head -n 1`.
+/// This is reported, but the shell keeps going, like other shells.
+fn print_output(bytes: &[u8]) {
+    let mut stdout = io::stdout();
+    // Flush right away, so the output of the next external command comes after it.
+    if let Err(e) = stdout.write_all(bytes).and_then(|()| stdout.flush()) {
+        eprintln!("Error: {}", e);
+    }
+}
+
+/// Read the output of the last command of a pipeline until it is done.
+fn read_all(mut stdout: impl Read) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    if let Err(e) = stdout.read_to_end(&mut bytes) {
+        eprintln!("Error: {}", e);
+    }
+    bytes
+}
+
+/// Wait for `child` to exit. If waiting fails, the command counts as failed.
+/// If it was killed by a signal, this is reported, see [`signals::report_signal`].
+fn wait(child: &mut Child) -> ExitStatus {
+    match child.wait() {
+        Ok(status) => signals::report_signal(status),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            builtins::output(1, Vec::new()).status
+        }
+    }
+}
+
+/// The stdin of the next command in a pipeline.
+enum PipeInput {
+    /// The output of a command which is done, e.g. a builtin.
+    Output(Output),
+    /// The stdout of a command which is still running.
+    Child(ChildStdout),
+    /// The output of a builtin which runs on its own thread.
+    Pipe(PipeReader),
+}
+
+/// A command of a pipeline which was started.
+enum Stage {
+    /// Builtins are done once they return.
+    Done(Output),
+    /// External commands run at the same time as the rest of the pipeline.
+    Running(Child),
+    /// Builtins which read their input run on their own thread and write into a pipe.
+    Streaming(JoinHandle<ExitStatus>, PipeReader),
+}
+
+impl Cmd {
+    /// Run a builtin, or start an external command.
+    ///
+    /// With `capture`, the stdout of an external command is a pipe, so that the shell or the
+    /// next command of the pipeline can read it. Otherwise, it is the stdout of the shell.
+    fn run(&self, mut input: Option<PipeInput>, capture: bool, shell: &mut Shell) -> Stage {
+        self.trace(shell);
+        let builtin = self.with_assignments(shell, |shell| self.run_builtin(&mut input, shell));
+        let result = match builtin {
+            Some(result) => result
+                .and_then(|output| self.redirect_output(output))
+                // Builtins like `cd` don't produce any output, but they still succeed.
+                .map(|output| {
+                    Stage::Done(output.unwrap_or_else(|| builtins::output(0, Vec::new())))
+                }),
+            None => self.spawn(input, capture),
+        };
+
+        match result {
+            Ok(Stage::Done(output)) => {
+                // Print stderr (if any)
+                std::io::stderr().write_all(&output.stderr).unwrap();
+                Stage::Done(output)
+            }
+            Ok(stage) => stage,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                Stage::Done(builtins::output(1, Vec::new()))
+            }
+        }
+    }
+
+    /// Whether the command is a builtin which reads its input until it ends.
+    fn is_filter(&self) -> bool {
+        FILTERS.contains(&self.binary.as_str())
+    }
+
+    /// Run a builtin which reads its input on its own thread, writing into a pipe.
+    ///
+    /// It reads and writes at the same time as the other commands of the pipeline, like an
+    /// external command. Otherwise, `yes | tr y n | head -n 1` would wait for the end of `yes`.
+    fn spawn_filter(self, mut input: Option<PipeInput>, shell: &Shell) -> Stage {
+        self.trace(shell);
+        let (reader, writer) = match io::pipe() {
+            Ok(pipe) => pipe,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return Stage::Done(builtins::output(1, Vec::new()));
+            }
+        };
+        let thread = std::thread::spawn(move || {
+            let result = self
+                .builtin_stdin(&mut input)
+                .and_then(|stdin| self.run_filter(stdin, writer));
+            match result {
+                Ok(status) => status,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    builtins::output(1, Vec::new()).status
+                }
+            }
+        });
+        Stage::Streaming(thread, reader)
+    }
+
+    /// Run a builtin which reads its input, see [`FILTERS`], and write its output to `pipe`.
+    fn run_filter(&self, stdin: Box<dyn BufRead>, pipe: PipeWriter) -> Result<ExitStatus> {
+        let mut stdout: Box<dyn Write> = match self.destinations()? {
+            [Destination::Default, _] => Box::new(pipe),
+            [Destination::Other, _] => Box::new(io::stderr()),
+            [Destination::File(file), _] => Box::new(file),
+        };
+        let result = match self.binary.as_str() {
+            // `tr` writes while it reads, so it works on input which never ends.
+            "tr" => builtins::Tr::new(&self.args)?
+                .translate(stdin, &mut stdout)
+                .map(|()| None),
+            "sort" => builtins::Sort::new(&self.args)?.run(stdin),
+            "head" => builtins::Head::new(&self.args)?.run(stdin),
+            "tail" => builtins::Tail::new(&self.args)?.run(stdin),
+            binary => unreachable!("{binary} is not a filter"),
+        }
+        .and_then(|output| {
+            let output = output.unwrap_or_else(|| builtins::output(0, Vec::new()));
+            stdout.write_all(&output.stdout)?;
+            Ok(output)
+        });
+        match result {
+            Ok(output) => {
+                io::stderr().write_all(&output.stderr)?;
+                Ok(output.status)
+            }
+            // The next command doesn't read anymore, e.g. `head` is done.
+            // External commands are killed by `SIGPIPE` then, the builtin stops quietly.
+            Err(e)
+                if e.downcast_ref::<io::Error>()
+                    .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe) =>
+            {
+                Ok(builtins::output(128 + libc::SIGPIPE, Vec::new()).status)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Run the command on its own, outside of a pipeline, and wait until it is done.
+    fn run_to_completion(&self, shell: &mut Shell) -> Output {
+        match self.run(None, true, shell) {
+            Stage::Done(output) => output,
+            Stage::Streaming(..) => unreachable!("only pipelines run builtins on their own thread"),
+            Stage::Running(child) => {
+                let _foreground = signals::Foreground::new(child.id());
+                match child.wait_with_output() {
+                    Ok(mut output) => {
+                        output.status = signals::report_signal(output.status);
+                        output
+                    }
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        builtins::output(1, Vec::new())
+                    }
+                }
+            }
+        }
+    }
+
+    /// Run the command if it is a builtin, see [`builtins::BUILTINS`]. Returns `None` otherwise.
+    fn run_builtin(
+        &self,
+        input: &mut Option<PipeInput>,
+        shell: &mut Shell,
+    ) -> Option<Result<Option<Output>>> {
+        // Only redirections, like `> file`: create or truncate the files, but run nothing.
+        if self.binary.is_empty() {
+            let result = self
+                .redirects
+                .iter()
+                .filter(|redirect| redirect.kind.is_file())
+                .try_for_each(|redirect| {
+                    redirect.open()?;
+                    Ok(())
+                });
+            return Some(result.map(|()| None));
+        }
+        // The command of `env` is started like an external command, see `Cmd::command`.
+        if self.binary == "env"
+            && builtins::Env::new(&self.args).is_ok_and(|env| env.command().is_some())
+        {
+            return None;
+        }
+        let builtin = builtins::find_builtin(&self.binary)?;
+        Some((builtin.run)(self, input, shell))
+    }
+
+    /// Print the expanded command to stderr if `set -x` is on.
+    ///
+    /// Each command of a pipeline or a chain is printed right before it runs.
+    /// Commands which are skipped because of `&&` or `||` are not printed.
+    fn trace(&self, shell: &Shell) {
+        if !shell.options.xtrace {
+            return;
+        }
+        let assignments = self
+            .assignments
+            .iter()
+            .map(|(name, value)| format!("{name}={}", quote_for_trace(value)));
+        let words = std::iter::once(&self.binary)
+            .chain(&self.args)
+            .filter(|word| !word.is_empty())
+            .map(|word| quote_for_trace(word));
+        let line: Vec<String> = assignments.chain(words).collect();
+        eprintln!("+ {}", line.join(" "));
+    }
+
+    /// Set the variables assigned before the command while `f` runs.
+    ///
+    /// While the command runs, the variables are exported, so that commands started
+    /// by builtins like `exec` get them too. Afterwards, the previous values are restored.
+    /// Assignments without a command, like `x=1`, and assignments before special builtins
+    /// are then kept as variables of the shell.
+    fn with_assignments<T>(&self, shell: &mut Shell, f: impl FnOnce(&mut Shell) -> T) -> T {
+        let previous: Vec<_> = self
+            .assignments
+            .iter()
+            .map(|(name, value)| {
+                let previous = std::env::var_os(name);
+                std::env::set_var(name, value);
+                (name, previous)
+            })
+            .collect();
+
+        let result = f(shell);
+
+        // Restore in reverse order, in case a variable is assigned twice.
+        for (name, value) in previous.into_iter().rev() {
+            match value {
+                Some(value) => std::env::set_var(name, value),
+                None => std::env::remove_var(name),
+            }
+        }
+        let keep =
+            self.binary.is_empty() || builtins::SPECIAL_BUILTINS.contains(&self.binary.as_str());
+        if keep {
+            for (name, value) in &self.assignments {
+                shell.variables.set(name, value);
+            }
+        }
+        result
+    }
+
+    /// The input of a builtin: a redirected file,
+    /// the output of the previous command or the stdin of the shell.
+    ///
+    /// The stdin of the shell is shared with the main loop, which reads the next line
+    /// from the same buffer. This way, `read` doesn't take away the following commands.
+    fn builtin_stdin(&self, input: &mut Option<PipeInput>) -> Result<Box<dyn BufRead>> {
+        let redirect = self
+            .redirects
+            .iter()
+            .rfind(|redirect| redirect.kind.fd() == 0);
+        Ok(match (redirect, input.take()) {
+            (Some(redirect), _) => match redirect.text() {
+                Some(text) => Box::new(io::Cursor::new(text)),
+                None => Box::new(BufReader::new(redirect.open()?)),
+            },
+            (None, Some(PipeInput::Output(output))) => Box::new(io::Cursor::new(output.stdout)),
+            (None, Some(PipeInput::Child(stdout))) => Box::new(BufReader::new(stdout)),
+            (None, Some(PipeInput::Pipe(stdout))) => Box::new(BufReader::new(stdout)),
+            (None, None) => Box::new(io::stdin().lock()),
+        })
+    }
+
+    /// Write the output of a builtin to the files it is redirected to.
+    /// External commands write to these files directly, see `configure`.
+    fn redirect_output(&self, output: Option<Output>) -> Result<Option<Output>> {
+        let Some(mut output) = output else {
+            return Ok(None);
+        };
+        let streams = [
+            std::mem::take(&mut output.stdout),
+            std::mem::take(&mut output.stderr),
+        ];
+        for (index, (bytes, destination)) in
+            streams.into_iter().zip(self.destinations()?).enumerate()
+        {
+            let index = match destination {
+                Destination::Default => index,
+                Destination::Other => 1 - index,
+                Destination::File(mut file) => {
+                    file.write_all(&bytes)?;
+                    continue;
+                }
+            };
+            let stream = if index == 0 {
+                &mut output.stdout
+            } else {
+                &mut output.stderr
+            };
+            stream.extend(bytes);
+        }
+        Ok(Some(output))
+    }
+
+    /// Where stdout and stderr of the command go, after applying the redirections in order.
+    ///
+    /// So `> file 2>&1` sends both to `file`, but `2>&1 > file` only stdout.
+    fn destinations(&self) -> Result<[Destination; 2]> {
+        let mut destinations = [Destination::Default, Destination::Default];
+        for redirect in &self.redirects {
+            let index = match redirect.kind.fd() {
+                1 => 0,
+                2 => 1,
+                _ => continue,
+            };
+            destinations[index] = match (redirect.kind.duplicated_fd(), &destinations[1 - index]) {
+                (None, _) => Destination::File(redirect.open()?),
+                (Some(_), Destination::File(file)) => Destination::File(file.try_clone()?),
+                (Some(_), Destination::Default) => Destination::Other,
+                (Some(_), Destination::Other) => Destination::Default,
+            };
+        }
+        Ok(destinations)
+    }
+
+    /// Start an external command, reading from `input`.
+    ///
+    /// Its stdout is a pipe with `capture`, see [`Cmd::run`]. Its stderr goes to the terminal.
+    /// A command which doesn't exist is done right away, with the exit code 127 like in bash.
+    fn spawn(&self, input: Option<PipeInput>, capture: bool) -> Result<Stage> {
+        let mut command = self.command()?;
+        if capture {
+            command.stdout(Stdio::piped());
+        }
+
+        let stdin = match input {
+            Some(PipeInput::Child(stdout)) => {
+                command.stdin(stdout);
+                None
+            }
+            Some(PipeInput::Pipe(stdout)) => {
+                command.stdin(stdout);
+                None
+            }
+            Some(PipeInput::Output(output)) => {
+                command.stdin(Stdio::piped());
+                Some(output.stdout)
+            }
+            None => None,
+        };
+        self.configure(&mut command)?;
+        // A here-string or here-document takes precedence over the pipe, just like a file.
+        let stdin = self.here_string().or(stdin);
+
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            // The next command of a pipeline reads an empty input then.
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let mut output = builtins::output(127, Vec::new());
+                let program = command.get_program().to_string_lossy();
+                output.stderr = format!("{program}: command not found\n").into_bytes();
+                return Ok(Stage::Done(output));
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if let (Some(bytes), Some(mut stdin)) = (stdin, child.stdin.take()) {
+            // Write from another thread. The command may produce output before it
+            // has read all of its input, and we must not block it while doing so.
+            // Errors mean that the command doesn't read its input, which is fine.
+            std::thread::spawn(move || stdin.write_all(&bytes));
+        }
+        Ok(Stage::Running(child))
+    }
+
+    /// Start the command as a background job.
+    ///
+    /// The job writes to the terminal directly, but it can't read from it.
+    /// Otherwise it would compete with the shell for the input of the user.
+    fn spawn_background(&self, jobs: &mut Jobs) -> Result<()> {
+        let mut command = self.command()?;
+        command.stdin(Stdio::null());
+        self.configure(&mut command)?;
+
+        // Run the job in its own process group, so that Ctrl-C in the
+        // terminal only interrupts the foreground command.
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+
+        let line = std::iter::once(&self.binary).chain(&self.args);
+        jobs.spawn(&mut command, line.cloned().collect::<Vec<_>>().join(" "))
+    }
+
+    /// The process which runs the command, with its arguments.
+    ///
+    /// `env` with a command only changes the environment of that command.
+    /// So instead of `env`, the command itself is started.
+    fn command(&self) -> Result<Command> {
+        if self.binary == "env" {
+            if let Some(command) = builtins::Env::new(&self.args)?.command() {
+                return Ok(command);
+            }
+        }
+        let mut command = Command::new(&self.binary);
+        command.args(&self.args);
+        Ok(command)
+    }
+
+    /// Add the variables and redirections to `command`.
+    fn configure(&self, command: &mut Command) -> Result<()> {
+        command.envs(self.assignments.iter().map(|(name, value)| (name, value)));
+
+        // Redirections take precedence over pipes.
+        for redirect in self
+            .redirects
+            .iter()
+            .filter(|redirect| redirect.kind.fd() == 0)
+        {
+            // The text of a here-string or here-document is written to the stdin
+            // of the process, see `spawn`.
+            if redirect.text().is_some() {
+                command.stdin(Stdio::piped());
+            } else {
+                command.stdin(redirect.open()?);
+            }
+        }
+        // With `2>&1`, stderr goes to the stdout of the shell, not into a pipe.
+        // This is the same as long as the command is the last one of the pipeline.
+        let [stdout, stderr] = self.destinations()?;
+        match stdout {
+            Destination::Default => {}
+            Destination::Other => {
+                command.stdout(io::stderr());
+            }
+            Destination::File(file) => {
+                command.stdout(file);
+            }
+        }
+        match stderr {
+            Destination::Default => {}
+            Destination::Other => {
+                command.stderr(io::stdout());
+            }
+            Destination::File(file) => {
+                command.stderr(file);
+            }
+        }
+        Ok(())
+    }
+
+    /// The text of the here-string or here-document, if it is the last redirection of stdin.
+    fn here_string(&self) -> Option<Vec<u8>> {
+        self.redirects
+            .iter()
+            .rfind(|redirect| redirect.kind.fd() == 0)
+            .and_then(Redirect::text)
+    }
+}
+
+/// Where stdout or stderr of a command goes.
+enum Destination {
+    /// Where it goes without redirections, e.g. into a pipe or to the terminal.
+    Default,
+    /// Where the other stream goes without redirections, for `2>&1` or `>&2`.
+    Other,
+    File(File),
+}
+
+impl Redirect {
+    /// The input of a here-string, like `hello\n` for `<<< hello`, or of a here-document.
+    /// `None` for redirections to files.
+    pub fn text(&self) -> Option<Vec<u8>> {
+        match self.kind {
+            RedirectKind::HereString => Some(format!("{}\n", self.target).into_bytes()),
+            kind if kind.is_here_document() => Some(self.target.clone().into_bytes()),
+            _ => None,
+        }
+    }
+
+    /// Open the file of the redirection, for reading or writing depending on its kind.
+    pub fn open(&self) -> io::Result<File> {
+        let mut options = OpenOptions::new();
+        match self.kind {
+            RedirectKind::Stdin => options.read(true),
+            RedirectKind::Stdout | RedirectKind::Stderr => {
+                options.write(true).create(true).truncate(true)
+            }
+            RedirectKind::AppendStdout | RedirectKind::AppendStderr => {
+                options.append(true).create(true)
+            }
+            _ => {
+                let message = "the redirection has no file";
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+            }
+        };
+        options.open(&self.target)
+    }
+}
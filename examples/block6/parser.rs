@@ -0,0 +1,1319 @@
+//! Turn a line of input into [`Chain`]s of commands.
+//!
+//! Instead of splitting the line on whitespace, the parser walks over it
+//! character by character. This way `ls|wc` is understood just like `ls | wc`.
+
+use crate::Result;
+use std::collections::{HashMap, VecDeque};
+
+// This struct doesn't use lifetimes to keep the code simple.
+// You can try to use `&str` instead of `String`
+// to avoid unnecessary allocations. 👍
+#[derive(PartialEq, Debug)]
+pub struct Cmd {
+    /// Variables set for this command, written as `NAME=value` before the binary.
+    pub assignments: Vec<(String, String)>,
+    pub binary: String,
+    pub args: Vec<String>,
+    pub redirects: Vec<Redirect>,
+}
+
+/// Redirect an input or output stream of a command to a file.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Redirect {
+    pub kind: RedirectKind,
+    /// The file to read from or write to, or the text of a here-string or here-document.
+    pub target: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RedirectKind {
+    /// `< file`
+    Stdin,
+    /// `<<< text`: the text and a newline are the stdin of the command.
+    HereString,
+    /// `<< DELIM`: the following lines up to `DELIM` are the stdin of the command.
+    HereDoc,
+    /// `<<- DELIM`: like `<<`, but leading tabs are removed from the lines.
+    IndentedHereDoc,
+    /// `> file`
+    Stdout,
+    /// `>> file`
+    AppendStdout,
+    /// `2> file`
+    Stderr,
+    /// `2>> file`
+    AppendStderr,
+    /// `2>&1`: stderr goes wherever stdout goes at this point.
+    StderrToStdout,
+    /// `>&2` or `1>&2`: stdout goes wherever stderr goes at this point.
+    StdoutToStderr,
+    /// `&> file`. The parser turns it into `> file 2>&1`.
+    StdoutAndStderr,
+}
+
+impl RedirectKind {
+    /// The symbols of all redirections, ordered so that longer symbols are tried first.
+    const SYMBOLS: [&'static str; 9] = ["<<<", "<<-", "<<", ">>", ">&1", ">&2", "&>", ">", "<"];
+
+    /// Return the symbol of the redirection at the start of `input`, if any.
+    fn symbol_at(input: &str) -> Option<&'static str> {
+        Self::SYMBOLS
+            .into_iter()
+            .find(|symbol| input.starts_with(symbol))
+    }
+
+    /// The file descriptor a redirection applies to if there is no number in front of it,
+    /// 0 for `<` and 1 for `>`.
+    fn default_fd(symbol: &str) -> i32 {
+        if symbol.starts_with('<') {
+            0
+        } else {
+            1
+        }
+    }
+
+    /// The redirection `symbol` of the file descriptor `fd`, e.g. [`Self::Stderr`] for `2>`.
+    ///
+    /// Returns `None` if the shell doesn't support it, like `0>`,
+    /// or if it doesn't change anything, like `1>&1`.
+    fn new(symbol: &str, fd: i32) -> Option<Self> {
+        let kind = match (symbol, fd) {
+            ("<", 0) => Self::Stdin,
+            ("<<<", 0) => Self::HereString,
+            ("<<", 0) => Self::HereDoc,
+            ("<<-", 0) => Self::IndentedHereDoc,
+            (">", 1) => Self::Stdout,
+            (">>", 1) => Self::AppendStdout,
+            (">", 2) => Self::Stderr,
+            (">>", 2) => Self::AppendStderr,
+            (">&1", 2) => Self::StderrToStdout,
+            (">&2", 1) => Self::StdoutToStderr,
+            ("&>", 1) => Self::StdoutAndStderr,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    /// How the redirection is written, e.g. `2>` for [`Self::Stderr`].
+    fn symbol(self) -> &'static str {
+        match self {
+            Self::Stdin => "<",
+            Self::HereString => "<<<",
+            Self::HereDoc => "<<",
+            Self::IndentedHereDoc => "<<-",
+            Self::Stdout => ">",
+            Self::AppendStdout => ">>",
+            Self::Stderr => "2>",
+            Self::AppendStderr => "2>>",
+            Self::StderrToStdout => "2>&1",
+            Self::StdoutToStderr => ">&2",
+            Self::StdoutAndStderr => "&>",
+        }
+    }
+
+    /// Whether the redirection is a here-document, written as `<<` or `<<-`.
+    pub fn is_here_document(self) -> bool {
+        matches!(self, Self::HereDoc | Self::IndentedHereDoc)
+    }
+
+    /// Whether the target of the redirection is a file which has to be opened.
+    pub fn is_file(self) -> bool {
+        matches!(
+            self,
+            Self::Stdin | Self::Stdout | Self::AppendStdout | Self::Stderr | Self::AppendStderr
+        )
+    }
+
+    /// The file descriptor which is copied, like 1 for `2>&1`.
+    pub fn duplicated_fd(self) -> Option<i32> {
+        match self {
+            Self::StderrToStdout => Some(1),
+            Self::StdoutToStderr => Some(2),
+            _ => None,
+        }
+    }
+
+    /// The file descriptor which is redirected.
+    pub fn fd(self) -> i32 {
+        match self {
+            Self::Stdin | Self::HereString | Self::HereDoc | Self::IndentedHereDoc => 0,
+            Self::Stdout | Self::AppendStdout | Self::StdoutToStderr | Self::StdoutAndStderr => 1,
+            Self::Stderr | Self::AppendStderr | Self::StderrToStdout => 2,
+        }
+    }
+}
+
+/// Commands connected by `|`. The stdout of each command is the stdin of the next one.
+#[derive(PartialEq, Debug)]
+pub struct Pipeline {
+    pub cmds: Vec<Cmd>,
+}
+
+#[derive(PartialEq, Debug)]
+pub enum Element {
+    /// `&&`
+    And,
+    /// `||`
+    Or,
+    /// A single command or several commands connected by `|`.
+    Pipeline(Pipeline),
+}
+
+/// A sequence of pipelines joined by `&&` and `||`.
+///
+/// Like in other shells, `&&` and `||` have the same precedence and are
+/// evaluated from left to right: `a && b || c` runs `c` if `a` or `b` fails.
+#[derive(PartialEq, Debug)]
+pub struct Chain {
+    pub elements: Vec<Element>,
+    /// The chain ends with `&`, so the shell doesn't wait for it.
+    pub background: bool,
+}
+
+/// An operator which can appear between two commands.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Operator {
+    /// `|`
+    Pipe,
+    /// `&&`
+    And,
+    /// `||`
+    Or,
+}
+
+impl Operator {
+    /// All operators, ordered so that longer symbols are tried first.
+    /// Otherwise `||` would be read as two pipes.
+    const ALL: [Self; 3] = [Self::And, Self::Or, Self::Pipe];
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Self::Pipe => "|",
+            Self::And => "&&",
+            Self::Or => "||",
+        }
+    }
+}
+
+/// Configure which characters have a special meaning for the [`Parser`].
+///
+/// The default configuration behaves like a regular shell: commands are
+/// separated by `;` and all [`Operator`]s are active.
+/// If you embed the parser in another program, you can disable operators
+/// to treat them as ordinary characters instead.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ParserConfig {
+    separator: char,
+    operators: Vec<Operator>,
+}
+
+impl Default for ParserConfig {
+    fn default() -> Self {
+        Self {
+            separator: ';',
+            operators: Operator::ALL.to_vec(),
+        }
+    }
+}
+
+// The shell itself only uses the default configuration.
+#[allow(dead_code)]
+impl ParserConfig {
+    /// Use `separator` instead of `;` to separate commands.
+    pub fn separator(mut self, separator: char) -> Self {
+        self.separator = separator;
+        self
+    }
+
+    /// Don't treat `operator` specially. It becomes part of the surrounding word.
+    pub fn disable(mut self, operator: Operator) -> Self {
+        self.operators.retain(|&o| o != operator);
+        self
+    }
+}
+
+impl ParserConfig {
+    /// Return the active operator at the start of `input`, if any.
+    fn operator_at(&self, input: &str) -> Option<Operator> {
+        Operator::ALL
+            .into_iter()
+            .filter(|o| self.operators.contains(o))
+            .find(|o| input.starts_with(o.symbol()))
+    }
+}
+
+#[derive(PartialEq, Debug)]
+enum Token {
+    Word(String),
+    Operator(Operator),
+    Redirect(RedirectKind),
+    Separator,
+    /// `&` ends a chain, like a separator, and runs it in the background.
+    Background,
+}
+
+/// Split `line` into words, operators and separators.
+fn tokenize(line: &str, config: &ParserConfig) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut word = String::new();
+    let mut rest = line;
+
+    while let Some(c) = rest.chars().next() {
+        // Quoted and escaped characters lose their special meaning.
+        // They are kept as they are and removed when the command is expanded.
+        let quoted = match c {
+            '\'' | '"' | '`' => quoted_len(rest),
+            // `$(ls -l)` and `$((1 + 2))` are single words, even with spaces or operators inside.
+            '$' if rest[1..].starts_with('(') => parenthesized_len(&rest[1..]) + 1,
+            '\\' => c.len_utf8() + rest[1..].chars().next().map_or(0, char::len_utf8),
+            _ => 0,
+        };
+        if quoted > 0 {
+            word.push_str(&rest[..quoted]);
+            rest = &rest[quoted..];
+            continue;
+        }
+
+        // A `#` at the start of a word comments out the rest of the line.
+        // This also skips the shebang line of scripts. In `echo a#b`, `#` is a normal character.
+        if c == '#' && word.is_empty() {
+            break;
+        }
+
+        if let Some(operator) = config.operator_at(rest) {
+            end_word(&mut tokens, &mut word);
+            tokens.push(Token::Operator(operator));
+            rest = &rest[operator.symbol().len()..];
+            continue;
+        }
+
+        if let Some(symbol) = RedirectKind::symbol_at(rest) {
+            // A single digit right in front of the redirection is the file descriptor,
+            // as in `2>`, `1>&2` or `0<`. In `a2>out` or `0>out`, it is part of a word.
+            let default_fd = RedirectKind::default_fd(symbol);
+            let fd = match word.as_str() {
+                "0" => Some(0),
+                "1" => Some(1),
+                "2" => Some(2),
+                _ => None,
+            }
+            .filter(|&fd| {
+                let unchanged = symbol.strip_prefix(">&") == Some(fd.to_string().as_str());
+                unchanged || RedirectKind::new(symbol, fd).is_some()
+            });
+            if fd.is_some() {
+                word.clear();
+            }
+            end_word(&mut tokens, &mut word);
+            // Duplicating a stream onto itself, like `>&1`, changes nothing.
+            if let Some(kind) = RedirectKind::new(symbol, fd.unwrap_or(default_fd)) {
+                tokens.push(Token::Redirect(kind));
+            }
+            rest = &rest[symbol.len()..];
+            continue;
+        }
+
+        if c == config.separator {
+            end_word(&mut tokens, &mut word);
+            tokens.push(Token::Separator);
+        } else if c == '&' {
+            // `&&` is an operator and was handled above.
+            end_word(&mut tokens, &mut word);
+            tokens.push(Token::Background);
+        } else if c.is_whitespace() {
+            end_word(&mut tokens, &mut word);
+        } else {
+            word.push(c);
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+    end_word(&mut tokens, &mut word);
+
+    tokens
+}
+
+/// Length in bytes of the quoted string at the start of `input`, including the quotes.
+/// If the closing quote is missing, the string extends to the end of `input`.
+fn quoted_len(input: &str) -> usize {
+    closed_quote_len(input).unwrap_or(input.len())
+}
+
+/// Like [`quoted_len`], but `None` if the closing quote is missing.
+fn closed_quote_len(input: &str) -> Option<usize> {
+    let mut chars = input.char_indices();
+    let (_, quote) = chars.next()?;
+    while let Some((i, c)) = chars.next() {
+        if c == quote {
+            return Some(i + c.len_utf8());
+        }
+        // Inside double quotes and backticks, a backslash escapes the next character.
+        if c == '\\' && quote != '\'' {
+            chars.next();
+        }
+    }
+    None
+}
+
+/// Length in bytes of the command at the start of `text`, up to the first line break
+/// outside of quotes. The lines after it contain the here-documents.
+///
+/// Returns `None` if a quote isn't closed, then the command continues on the next line.
+fn command_len(text: &str) -> Option<usize> {
+    let mut i = 0;
+    while let Some(c) = text[i..].chars().next() {
+        i += match c {
+            '\'' | '"' | '`' => closed_quote_len(&text[i..])?,
+            '\\' => c.len_utf8() + text[i + 1..].chars().next().map_or(0, char::len_utf8),
+            '\n' => return Some(i),
+            // Quotes in comments, like `# don't`, don't count.
+            '#' if text[..i]
+                .chars()
+                .next_back()
+                .is_none_or(char::is_whitespace) =>
+            {
+                return Some(text[i..].find('\n').map_or(text.len(), |end| i + end));
+            }
+            _ => c.len_utf8(),
+        };
+    }
+    Some(text.len())
+}
+
+/// Length in bytes of the parenthesized text at the start of `input`, including the parentheses.
+/// Parentheses inside quotes don't count. If the closing parenthesis is missing,
+/// the text extends to the end of `input`.
+fn parenthesized_len(input: &str) -> usize {
+    let mut depth = 0;
+    let mut i = 0;
+    while let Some(c) = input[i..].chars().next() {
+        match c {
+            '\'' | '"' => {
+                i += quoted_len(&input[i..]);
+                continue;
+            }
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return i + 1;
+                }
+            }
+            _ => {}
+        }
+        i += c.len_utf8();
+    }
+    input.len()
+}
+
+fn end_word(tokens: &mut Vec<Token>, word: &mut String) {
+    if !word.is_empty() {
+        tokens.push(Token::Word(std::mem::take(word)));
+    }
+}
+
+/// Remove the quotes and backslashes from `word`, e.g. the delimiter `'EOF'` becomes `EOF`.
+fn unquote(word: &str) -> String {
+    word.chars()
+        .filter(|c| !matches!(c, '\'' | '"' | '\\'))
+        .collect()
+}
+
+/// Join `line` with the following lines while it ends with a backslash
+/// or contains a quote which isn't closed. The lines are read with `next_line`.
+///
+/// The backslash and the line break are removed, so `echo a\` followed by `b` is `echo ab`.
+/// This allows to split long commands. An escaped backslash, as in `echo a\\`, is kept.
+/// Inside quotes, the line break is kept: it becomes part of the quoted word.
+pub fn read_continued_lines(line: &str, mut next_line: impl FnMut() -> Option<String>) -> String {
+    let mut text = line.trim_end_matches(['\n', '\r']).to_string();
+    loop {
+        if command_len(&text).is_none() {
+            // At the end of the input, the parser closes the quote.
+            let Some(next) = next_line() else {
+                return text;
+            };
+            text.push('\n');
+            text.push_str(next.trim_end_matches(['\n', '\r']));
+            continue;
+        }
+        let backslashes = text.len() - text.trim_end_matches('\\').len();
+        if backslashes.is_multiple_of(2) {
+            return text;
+        }
+        text.pop();
+        // Like in other shells, a backslash at the end of the input is dropped.
+        let Some(next) = next_line() else {
+            return text;
+        };
+        text.push_str(next.trim_end_matches(['\n', '\r']));
+    }
+}
+
+/// Append the here-documents of `line` to it. Their lines are read with `next_line`.
+///
+/// For `cat << EOF`, lines are read up to and including the line `EOF`.
+/// The [`Parser`] finds them after the first line of its input.
+pub fn read_here_documents(line: &str, mut next_line: impl FnMut() -> Option<String>) -> String {
+    let mut text = line.to_string();
+    for pair in tokenize(line, &ParserConfig::default()).windows(2) {
+        let [Token::Redirect(kind), Token::Word(delimiter)] = pair else {
+            continue;
+        };
+        if !kind.is_here_document() {
+            continue;
+        }
+        let delimiter = unquote(delimiter);
+        while let Some(next) = next_line() {
+            let next = next.trim_end_matches(['\n', '\r']);
+            text.push('\n');
+            text.push_str(next);
+            let next = match kind {
+                RedirectKind::IndentedHereDoc => next.trim_start_matches('\t'),
+                _ => next,
+            };
+            if next == delimiter {
+                break;
+            }
+        }
+    }
+    text
+}
+
+/// Parse [`Chain`]s from a string.
+pub struct Parser {
+    current: usize,
+    tokens: Vec<Token>,
+    config: ParserConfig,
+    /// The lines after the first one, which contain the here-documents.
+    here_documents: VecDeque<String>,
+}
+
+impl Parser {
+    /// Create a parser with the default [`ParserConfig`].
+    pub fn new(line: &str) -> Self {
+        Self::with_config(line, &ParserConfig::default())
+    }
+
+    /// Create a parser which respects the given `config`.
+    ///
+    /// Only the first line contains commands. It is followed by the lines
+    /// of the here-documents, see [`read_here_documents`].
+    pub fn with_config(line: &str, config: &ParserConfig) -> Self {
+        let end = command_len(line).unwrap_or(line.len());
+        let (line, here_documents) = (&line[..end], line.get(end + 1..).unwrap_or(""));
+        Self {
+            tokens: tokenize(line, config),
+            current: 0,
+            config: config.clone(),
+            here_documents: here_documents.lines().map(String::from).collect(),
+        }
+    }
+
+    /// Replace command names which are keys of `aliases` with their value.
+    ///
+    /// Only words in command position are replaced, so `echo ll` stays as is.
+    /// The value of an alias isn't expanded again. This allows
+    /// aliases like `ls=ls -a` without looping forever.
+    pub fn with_aliases(mut self, aliases: &HashMap<String, String>) -> Self {
+        let mut tokens = Vec::with_capacity(self.tokens.len());
+        let mut command_position = true;
+        for token in std::mem::take(&mut self.tokens) {
+            match token {
+                Token::Word(word) if command_position => {
+                    command_position = false;
+                    match aliases.get(&word) {
+                        Some(value) => tokens.extend(tokenize(value, &self.config)),
+                        None => tokens.push(Token::Word(word)),
+                    }
+                }
+                Token::Word(word) => tokens.push(Token::Word(word)),
+                // A new command starts after every operator and separator.
+                token => {
+                    command_position = true;
+                    tokens.push(token);
+                }
+            }
+        }
+        self.tokens = tokens;
+        self
+    }
+
+    /// Parse all chains of the line.
+    /// Empty chains, e.g. between two consecutive separators, are skipped.
+    ///
+    /// Fails on a syntax error, like a redirection without a file.
+    pub fn parse(&mut self) -> Result<Vec<Chain>> {
+        let mut chains = vec![];
+        while self.current < self.tokens.len() {
+            chains.extend(self.parse_chain()?);
+        }
+        Ok(chains)
+    }
+
+    fn parse_chain(&mut self) -> Result<Option<Chain>> {
+        let mut elements = vec![];
+        while let Some(e) = self.parse_next()? {
+            elements.push(e);
+        }
+        let background = self.tokens.get(self.current) == Some(&Token::Background);
+        // Skip the separator which ended the chain (if any).
+        self.current += 1;
+        if elements.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(Chain {
+                elements,
+                background,
+            }))
+        }
+    }
+
+    fn parse_next(&mut self) -> Result<Option<Element>> {
+        let Some(token) = self.tokens.get(self.current) else {
+            return Ok(None);
+        };
+        Ok(match token {
+            Token::Separator | Token::Background => None,
+            Token::Operator(Operator::And) => {
+                self.current += 1;
+                Some(Element::And)
+            }
+            Token::Operator(Operator::Or) => {
+                self.current += 1;
+                Some(Element::Or)
+            }
+            Token::Operator(Operator::Pipe) | Token::Word(_) | Token::Redirect(_) => {
+                Some(Element::Pipeline(self.parse_pipeline()?))
+            }
+        })
+    }
+
+    /// Parse commands until we find an operator other than `|`, or a separator.
+    /// Pipes without a command before or after them are skipped.
+    fn parse_pipeline(&mut self) -> Result<Pipeline> {
+        let mut cmds = vec![];
+        loop {
+            match self.tokens.get(self.current) {
+                Some(Token::Operator(Operator::Pipe)) => self.current += 1,
+                Some(Token::Word(_) | Token::Redirect(_)) => cmds.push(self.parse_cmd()?),
+                _ => break,
+            }
+        }
+        Ok(Pipeline { cmds })
+    }
+
+    fn parse_cmd(&mut self) -> Result<Cmd> {
+        let mut assignments = vec![];
+        let mut words: Vec<String> = vec![];
+        let mut redirects = vec![];
+        // Collect words and redirections until we find an operator or a separator.
+        loop {
+            match self.tokens.get(self.current) {
+                Some(Token::Word(word)) => match parse_assignment(word) {
+                    // Assignments are only recognized before the binary:
+                    // in `echo FOO=bar`, `FOO=bar` is a normal argument.
+                    Some(assignment) if words.is_empty() => assignments.push(assignment),
+                    _ => words.push(word.to_string()),
+                },
+                Some(Token::Redirect(kind)) if kind.duplicated_fd().is_some() => {
+                    // `2>&1` and `>&2` don't have a file.
+                    redirects.push(Redirect {
+                        kind: *kind,
+                        target: String::new(),
+                    });
+                }
+                Some(Token::Redirect(kind)) => {
+                    let kind = *kind;
+                    // The word after the redirection is the file.
+                    let target = match self.tokens.get(self.current + 1) {
+                        Some(Token::Word(target)) => target,
+                        next => {
+                            let next = match next {
+                                Some(Token::Operator(operator)) => operator.symbol().to_string(),
+                                Some(Token::Redirect(kind)) => kind.symbol().to_string(),
+                                Some(Token::Separator) => self.config.separator.to_string(),
+                                Some(Token::Background) => "&".to_string(),
+                                Some(Token::Word(_)) | None => "newline".to_string(),
+                            };
+                            return Err(
+                                format!("syntax error near unexpected token `{next}`").into()
+                            );
+                        }
+                    };
+                    // For here-documents, it is the delimiter of the text instead.
+                    let target = if kind.is_here_document() {
+                        self.here_document(&target.clone(), kind)
+                    } else {
+                        target.to_string()
+                    };
+                    if kind == RedirectKind::StdoutAndStderr {
+                        redirects.push(Redirect {
+                            kind: RedirectKind::Stdout,
+                            target,
+                        });
+                        redirects.push(Redirect {
+                            kind: RedirectKind::StderrToStdout,
+                            target: String::new(),
+                        });
+                    } else {
+                        redirects.push(Redirect { kind, target });
+                    }
+                    self.current += 1;
+                }
+                _ => break,
+            }
+            self.current += 1;
+        }
+
+        let mut words = words.into_iter();
+        Ok(Cmd {
+            assignments,
+            binary: words.next().unwrap_or_default(),
+            args: words.collect(),
+            redirects,
+        })
+    }
+
+    /// Take the lines of the next here-document, up to the line `delimiter`.
+    ///
+    /// The text is expanded later, like a word. If the delimiter is quoted, as in `<< 'EOF'`,
+    /// the text is used as it is, so its `$`, `` ` `` and `\` are escaped.
+    fn here_document(&mut self, delimiter: &str, kind: RedirectKind) -> String {
+        let literal = delimiter.contains(['\'', '"', '\\']);
+        let delimiter = unquote(delimiter);
+        let mut text = String::new();
+        while let Some(line) = self.here_documents.pop_front() {
+            let line = match kind {
+                RedirectKind::IndentedHereDoc => line.trim_start_matches('\t'),
+                _ => &line,
+            };
+            if line == delimiter {
+                break;
+            }
+            if literal {
+                for c in line.chars() {
+                    if matches!(c, '$' | '`' | '\\') {
+                        text.push('\\');
+                    }
+                    text.push(c);
+                }
+            } else {
+                text.push_str(line);
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+/// Split a word like `NAME=value` into the name and the value.
+///
+/// Returns `None` if the word is not an assignment. The name must be a valid
+/// variable name, so `=foo`, `1=foo` or `'FOO'=bar` are normal words.
+fn parse_assignment(word: &str) -> Option<(String, String)> {
+    let (name, value) = word.split_once('=')?;
+    is_name(name).then(|| (name.to_string(), value.to_string()))
+}
+
+/// Whether `name` can be the name of a variable, like `FOO_1`.
+pub fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_chains(line: &str) -> Vec<Chain> {
+        Parser::new(line).parse().unwrap()
+    }
+
+    fn simple_cmd(binary: &str, args: &[&str]) -> Cmd {
+        Cmd {
+            assignments: vec![],
+            binary: binary.to_string(),
+            args: args.iter().map(|s| s.to_string()).collect(),
+            redirects: vec![],
+        }
+    }
+
+    fn pipeline(cmds: Vec<Cmd>) -> Element {
+        Element::Pipeline(Pipeline { cmds })
+    }
+
+    /// A pipeline with a single command.
+    fn cmd(binary: &str, args: &[&str]) -> Element {
+        pipeline(vec![simple_cmd(binary, args)])
+    }
+
+    #[test]
+    fn no_cmd_is_parsed_from_empty_line() {
+        assert_eq!(parse_chains(""), vec![]);
+    }
+
+    #[test]
+    fn comments_are_ignored() {
+        assert_eq!(parse_chains("# ls -l"), vec![]);
+        assert_eq!(parse_chains("#!/usr/bin/env rush"), vec![]);
+        assert_eq!(
+            parse_chains("echo a#b '#c' #d; ls"),
+            vec![Chain {
+                background: false,
+                elements: vec![cmd("echo", &["a#b", "'#c'"])]
+            }]
+        );
+    }
+
+    #[test]
+    fn cmd_with_no_args_is_parsed() {
+        assert_eq!(
+            parse_chains("ls"),
+            vec![Chain {
+                background: false,
+                elements: vec![cmd("ls", &[])]
+            }]
+        );
+    }
+
+    #[test]
+    fn cmd_with_args_is_parsed() {
+        assert_eq!(
+            parse_chains("ls -l"),
+            vec![Chain {
+                background: false,
+                elements: vec![cmd("ls", &["-l"])]
+            }]
+        );
+    }
+
+    #[test]
+    fn cmds_are_parsed() {
+        assert_eq!(
+            parse_chains("ls; echo hello"),
+            vec![
+                Chain {
+                    background: false,
+                    elements: vec![cmd("ls", &[])]
+                },
+                Chain {
+                    background: false,
+                    elements: vec![cmd("echo", &["hello"])]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn pipe_is_parsed() {
+        assert_eq!(
+            parse_chains("ls | wc -l"),
+            vec![Chain {
+                background: false,
+                elements: vec![pipeline(vec![
+                    simple_cmd("ls", &[]),
+                    simple_cmd("wc", &["-l"])
+                ])]
+            }]
+        );
+    }
+
+    #[test]
+    fn operators_dont_need_surrounding_whitespace() {
+        assert_eq!(
+            parse_chains("echo a|b&&c||d"),
+            vec![Chain {
+                background: false,
+                elements: vec![
+                    pipeline(vec![simple_cmd("echo", &["a"]), simple_cmd("b", &[])]),
+                    Element::And,
+                    cmd("c", &[]),
+                    Element::Or,
+                    cmd("d", &[]),
+                ]
+            }]
+        );
+    }
+
+    #[test]
+    fn quoted_operators_are_part_of_the_word() {
+        assert_eq!(
+            parse_chains(r#"echo 'a | b' "c;d" e\&\&f "g\"|" |wc"#),
+            vec![Chain {
+                background: false,
+                elements: vec![pipeline(vec![
+                    simple_cmd("echo", &["'a | b'", r#""c;d""#, r"e\&\&f", r#""g\"|""#]),
+                    simple_cmd("wc", &[])
+                ])]
+            }]
+        );
+    }
+
+    #[test]
+    fn unterminated_quote_extends_to_end_of_line() {
+        assert_eq!(
+            parse_chains("echo 'a; b"),
+            vec![Chain {
+                background: false,
+                elements: vec![cmd("echo", &["'a; b"])]
+            }]
+        );
+    }
+
+    #[test]
+    fn redirections_are_parsed() {
+        assert_eq!(
+            parse_chains("sort < in>out 2>>err"),
+            vec![Chain {
+                background: false,
+                elements: vec![pipeline(vec![Cmd {
+                    assignments: vec![],
+                    binary: "sort".to_string(),
+                    args: vec![],
+                    redirects: vec![
+                        Redirect {
+                            kind: RedirectKind::Stdin,
+                            target: "in".to_string()
+                        },
+                        Redirect {
+                            kind: RedirectKind::Stdout,
+                            target: "out".to_string()
+                        },
+                        Redirect {
+                            kind: RedirectKind::AppendStderr,
+                            target: "err".to_string()
+                        },
+                    ]
+                }])]
+            }]
+        );
+    }
+
+    #[test]
+    fn here_string_is_parsed() {
+        assert_eq!(
+            parse_chains("wc -c <<< 'hello world'"),
+            vec![Chain {
+                background: false,
+                elements: vec![pipeline(vec![Cmd {
+                    redirects: vec![Redirect {
+                        kind: RedirectKind::HereString,
+                        target: "'hello world'".to_string(),
+                    }],
+                    ..simple_cmd("wc", &["-c"])
+                }])],
+            }]
+        );
+    }
+
+    #[test]
+    fn trailing_backslash_continues_the_line() {
+        let mut lines = ["b \\\n", "c\n", "not read"].into_iter().map(String::from);
+        assert_eq!(
+            read_continued_lines("echo a\\\n", || lines.next()),
+            "echo ab c"
+        );
+        assert_eq!(read_continued_lines("echo a\\\\", || None), "echo a\\\\");
+        assert_eq!(read_continued_lines("echo a\\", || None), "echo a");
+    }
+
+    #[test]
+    fn unclosed_quotes_continue_the_line() {
+        let mut lines = ["b' \"c\n", "d\" e\n", "not read"]
+            .into_iter()
+            .map(String::from);
+        let text = read_continued_lines("echo 'a", || lines.next());
+        assert_eq!(text, "echo 'a\nb' \"c\nd\" e");
+        assert_eq!(
+            parse_chains(&text),
+            vec![Chain {
+                background: false,
+                elements: vec![cmd("echo", &["'a\nb'", "\"c\nd\"", "e"])]
+            }]
+        );
+        // Quotes in comments and escaped quotes don't need to be closed.
+        assert_eq!(
+            read_continued_lines("echo \\' # don't", || None),
+            "echo \\' # don't"
+        );
+    }
+
+    #[test]
+    fn here_documents_are_read_after_the_line() {
+        let mut lines = ["one", "\ttwo", "EOF", "\tthree", "\tEND", "not read"]
+            .into_iter()
+            .map(String::from);
+        let text = read_here_documents("cat << EOF; cat <<- 'END'", || lines.next());
+        assert_eq!(
+            text,
+            "cat << EOF; cat <<- 'END'\none\n\ttwo\nEOF\n\tthree\n\tEND"
+        );
+        assert_eq!(
+            parse_chains(&text),
+            vec![
+                Chain {
+                    background: false,
+                    elements: vec![pipeline(vec![Cmd {
+                        redirects: vec![Redirect {
+                            kind: RedirectKind::HereDoc,
+                            target: "one\n\ttwo\n".to_string(),
+                        }],
+                        ..simple_cmd("cat", &[])
+                    }])],
+                },
+                Chain {
+                    background: false,
+                    elements: vec![pipeline(vec![Cmd {
+                        redirects: vec![Redirect {
+                            kind: RedirectKind::IndentedHereDoc,
+                            target: "three\n".to_string(),
+                        }],
+                        ..simple_cmd("cat", &[])
+                    }])],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn text_of_here_documents_with_quoted_delimiters_is_escaped() {
+        let line = "cat << \"EOF\"; cat << E\\OF\n$x `ls` \\\nEOF\n$x\nEOF";
+        let targets: Vec<_> = parse_chains(line)
+            .iter()
+            .map(|chain| match &chain.elements[0] {
+                Element::Pipeline(pipeline) => pipeline.cmds[0].redirects[0].target.clone(),
+                _ => panic!("expected a pipeline"),
+            })
+            .collect();
+        assert_eq!(targets, ["\\$x \\`ls\\` \\\\\n", "\\$x\n"]);
+    }
+
+    #[test]
+    fn duplicated_streams_are_parsed() {
+        let redirect = |kind, target: &str| Redirect {
+            kind,
+            target: target.to_string(),
+        };
+        assert_eq!(
+            parse_chains("ls 2>&1 >out; ls &>both >&2"),
+            vec![
+                Chain {
+                    background: false,
+                    elements: vec![pipeline(vec![Cmd {
+                        redirects: vec![
+                            redirect(RedirectKind::StderrToStdout, ""),
+                            redirect(RedirectKind::Stdout, "out"),
+                        ],
+                        ..simple_cmd("ls", &[])
+                    }])],
+                },
+                Chain {
+                    background: false,
+                    elements: vec![pipeline(vec![Cmd {
+                        redirects: vec![
+                            redirect(RedirectKind::Stdout, "both"),
+                            redirect(RedirectKind::StderrToStdout, ""),
+                            redirect(RedirectKind::StdoutToStderr, ""),
+                        ],
+                        ..simple_cmd("ls", &[])
+                    }])],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn arithmetic_expansion_is_one_word() {
+        assert_eq!(
+            parse_chains("echo $(( (1 + 2) * 3 ))x | wc"),
+            vec![Chain {
+                background: false,
+                elements: vec![pipeline(vec![
+                    simple_cmd("echo", &["$(( (1 + 2) * 3 ))x"]),
+                    simple_cmd("wc", &[]),
+                ])],
+            }]
+        );
+    }
+
+    #[test]
+    fn command_substitutions_are_one_word() {
+        assert_eq!(
+            parse_chains("echo $(ls | wc -l) `date +%Y`"),
+            vec![Chain {
+                background: false,
+                elements: vec![cmd("echo", &["$(ls | wc -l)", "`date +%Y`"])],
+            }]
+        );
+    }
+
+    #[test]
+    fn two_is_only_a_file_descriptor_right_before_the_redirection() {
+        assert_eq!(
+            parse_chains("echo 2 >out"),
+            vec![Chain {
+                background: false,
+                elements: vec![pipeline(vec![Cmd {
+                    assignments: vec![],
+                    binary: "echo".to_string(),
+                    args: vec!["2".to_string()],
+                    redirects: vec![Redirect {
+                        kind: RedirectKind::Stdout,
+                        target: "out".to_string()
+                    }]
+                }])]
+            }]
+        );
+    }
+
+    #[test]
+    fn redirection_needs_a_file() {
+        for (line, token) in [
+            ("echo hi >", "newline"),
+            ("cat < | wc", "|"),
+            ("echo hi >> ; ls", ";"),
+            ("echo 2> >&2", ">&2"),
+        ] {
+            let error = Parser::new(line).parse().unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                format!("syntax error near unexpected token `{token}`")
+            );
+        }
+    }
+
+    #[test]
+    fn file_descriptors_are_parsed_for_all_redirections() {
+        let redirect = |kind, target: &str| Redirect {
+            kind,
+            target: target.to_string(),
+        };
+        assert_eq!(
+            parse_chains("cat 0<in 1>out 1>>log 2>>err 1>&2 2>&2 >&1"),
+            vec![Chain {
+                background: false,
+                elements: vec![pipeline(vec![Cmd {
+                    redirects: vec![
+                        redirect(RedirectKind::Stdin, "in"),
+                        redirect(RedirectKind::Stdout, "out"),
+                        redirect(RedirectKind::AppendStdout, "log"),
+                        redirect(RedirectKind::AppendStderr, "err"),
+                        redirect(RedirectKind::StdoutToStderr, ""),
+                    ],
+                    ..simple_cmd("cat", &[])
+                }])],
+            }]
+        );
+        // Unsupported file descriptors are part of the words.
+        assert_eq!(
+            parse_chains("echo 0>out a1>b"),
+            vec![Chain {
+                background: false,
+                elements: vec![pipeline(vec![Cmd {
+                    redirects: vec![
+                        redirect(RedirectKind::Stdout, "out"),
+                        redirect(RedirectKind::Stdout, "b"),
+                    ],
+                    ..simple_cmd("echo", &["0", "a1"])
+                }])],
+            }]
+        );
+    }
+
+    #[test]
+    fn disabled_operator_is_a_literal() {
+        let config = ParserConfig::default().disable(Operator::Pipe);
+        assert_eq!(
+            Parser::with_config("echo a|b", &config).parse().unwrap(),
+            vec![Chain {
+                background: false,
+                elements: vec![cmd("echo", &["a|b"])]
+            }]
+        );
+    }
+
+    #[test]
+    fn disabling_pipe_keeps_or() {
+        let config = ParserConfig::default().disable(Operator::Pipe);
+        assert_eq!(
+            Parser::with_config("a || b", &config).parse().unwrap(),
+            vec![Chain {
+                background: false,
+                elements: vec![cmd("a", &[]), Element::Or, cmd("b", &[])]
+            }]
+        );
+    }
+
+    #[test]
+    fn aliases_are_expanded_in_command_position() {
+        let aliases = HashMap::from([("ll".to_string(), "ls -l".to_string())]);
+        assert_eq!(
+            Parser::new("ll src; echo ll | ll")
+                .with_aliases(&aliases)
+                .parse()
+                .unwrap(),
+            vec![
+                Chain {
+                    background: false,
+                    elements: vec![cmd("ls", &["-l", "src"])]
+                },
+                Chain {
+                    background: false,
+                    elements: vec![pipeline(vec![
+                        simple_cmd("echo", &["ll"]),
+                        simple_cmd("ls", &["-l"])
+                    ])]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn alias_is_not_expanded_recursively() {
+        let aliases = HashMap::from([("ls".to_string(), "ls -a".to_string())]);
+        assert_eq!(
+            Parser::new("ls").with_aliases(&aliases).parse().unwrap(),
+            vec![Chain {
+                background: false,
+                elements: vec![cmd("ls", &["-a"])]
+            }]
+        );
+    }
+
+    #[test]
+    fn custom_separator_is_used() {
+        let config = ParserConfig::default().separator(',');
+        assert_eq!(
+            Parser::with_config("ls, echo a;b", &config)
+                .parse()
+                .unwrap(),
+            vec![
+                Chain {
+                    background: false,
+                    elements: vec![cmd("ls", &[])]
+                },
+                Chain {
+                    background: false,
+                    elements: vec![cmd("echo", &["a;b"])]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn leading_assignments_are_parsed() {
+        assert_eq!(
+            parse_chains("FOO=bar BAZ='a b' echo FOO=bar"),
+            vec![Chain {
+                background: false,
+                elements: vec![pipeline(vec![Cmd {
+                    assignments: vec![
+                        ("FOO".to_string(), "bar".to_string()),
+                        ("BAZ".to_string(), "'a b'".to_string()),
+                    ],
+                    binary: "echo".to_string(),
+                    args: vec!["FOO=bar".to_string()],
+                    redirects: vec![],
+                }])]
+            }]
+        );
+    }
+
+    #[test]
+    fn assignments_without_a_binary_are_parsed() {
+        assert_eq!(
+            parse_chains("FOO=bar"),
+            vec![Chain {
+                background: false,
+                elements: vec![pipeline(vec![Cmd {
+                    assignments: vec![("FOO".to_string(), "bar".to_string())],
+                    binary: String::new(),
+                    args: vec![],
+                    redirects: vec![],
+                }])]
+            }]
+        );
+    }
+
+    #[test]
+    fn invalid_names_are_not_assignments() {
+        assert_eq!(
+            parse_chains("1=a =b 'c'=d"),
+            vec![Chain {
+                background: false,
+                elements: vec![cmd("1=a", &["=b", "'c'=d"])]
+            }]
+        );
+    }
+
+    #[test]
+    fn ampersand_runs_the_chain_in_the_background() {
+        assert_eq!(
+            parse_chains("sleep 1 & echo a&&echo b"),
+            vec![
+                Chain {
+                    background: true,
+                    elements: vec![cmd("sleep", &["1"])]
+                },
+                Chain {
+                    background: false,
+                    elements: vec![cmd("echo", &["a"]), Element::And, cmd("echo", &["b"])]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn pipelines_are_joined_by_and_and_or() {
+        assert_eq!(
+            parse_chains("grep foo file | wc -l && echo done || a | b"),
+            vec![Chain {
+                background: false,
+                elements: vec![
+                    pipeline(vec![
+                        simple_cmd("grep", &["foo", "file"]),
+                        simple_cmd("wc", &["-l"])
+                    ]),
+                    Element::And,
+                    cmd("echo", &["done"]),
+                    Element::Or,
+                    pipeline(vec![simple_cmd("a", &[]), simple_cmd("b", &[])]),
+                ]
+            }]
+        );
+    }
+
+    #[test]
+    fn pipes_without_commands_are_skipped() {
+        assert_eq!(
+            parse_chains("| a || b |"),
+            vec![Chain {
+                background: false,
+                elements: vec![cmd("a", &[]), Element::Or, cmd("b", &[])]
+            }]
+        );
+    }
+
+    #[test]
+    fn redirections_without_command_are_parsed() {
+        assert_eq!(
+            parse_chains("> out"),
+            vec![Chain {
+                background: false,
+                elements: vec![pipeline(vec![Cmd {
+                    assignments: vec![],
+                    binary: "".to_string(),
+                    args: vec![],
+                    redirects: vec![Redirect {
+                        kind: RedirectKind::Stdout,
+                        target: "out".to_string()
+                    }]
+                }])]
+            }]
+        );
+    }
+}
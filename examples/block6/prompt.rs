@@ -0,0 +1,172 @@
+//! The prompt which is shown before reading a line.
+
+use std::{
+    env, fs,
+    io::{self, IsTerminal, Write},
+    path::Path,
+};
+
+/// The prompt to show if `PS1` is not set.
+const DEFAULT_PROMPT: &str = "> ";
+
+/// If `stdout` is printed to a terminal, print a prompt.
+/// Otherwise, do nothing. This allows to redirect the shell `stdout`
+/// to a file or another process, without the prompt being printed.
+pub fn show_prompt(ps1: Option<&str>, last_status: Option<i32>) {
+    let mut stdout = io::stdout();
+    if stdout.is_terminal() {
+        write!(stdout, "{}", render_prompt(ps1, last_status)).unwrap();
+        // Flush stdout to ensure the prompt is displayed.
+        stdout.flush().expect("can't flush stdout");
+    }
+}
+
+/// Build the prompt from the value of the `PS1` variable.
+///
+/// If the last command failed, its exit code is shown in front of the prompt.
+pub fn render_prompt(ps1: Option<&str>, last_status: Option<i32>) -> String {
+    let prompt = match ps1 {
+        Some(ps1) => {
+            let cwd = env::current_dir().unwrap_or_default();
+            expand_escapes(ps1, &cwd)
+        }
+        None => DEFAULT_PROMPT.to_string(),
+    };
+    let color = io::stdout().is_terminal();
+    format!("{}{prompt}", status_marker(last_status, color))
+}
+
+/// Show a failed exit code like `[1] `, in red if `color` is set.
+/// Successful commands don't get a marker.
+fn status_marker(last_status: Option<i32>, color: bool) -> String {
+    match last_status {
+        Some(code) if code != 0 && color => format!("\x1b[31m[{code}]\x1b[0m "),
+        Some(code) if code != 0 => format!("[{code}] "),
+        _ => String::new(),
+    }
+}
+
+/// Expand the escape sequences in `ps1`:
+///
+/// - `\w`: the current working directory
+/// - `\g`: the current git branch like ` (main)`, or nothing outside of a repository
+/// - `\$`: a literal `$`
+///
+/// Unknown escape sequences are kept as they are.
+fn expand_escapes(ps1: &str, cwd: &Path) -> String {
+    let mut prompt = String::new();
+    let mut chars = ps1.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            prompt.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('w') => prompt.push_str(&cwd.to_string_lossy()),
+            Some('g') => {
+                if let Some(branch) = git_branch(cwd) {
+                    prompt.push_str(&format!(" ({branch})"));
+                }
+            }
+            Some('$') => prompt.push('$'),
+            Some(other) => {
+                prompt.push('\\');
+                prompt.push(other);
+            }
+            None => prompt.push('\\'),
+        }
+    }
+    prompt
+}
+
+/// The git branch checked out in the repository containing `dir`, if any.
+///
+/// Instead of running `git`, which would slow down every prompt,
+/// only the `HEAD` file of the first `.git` directory above `dir` is read.
+fn git_branch(dir: &Path) -> Option<String> {
+    dir.ancestors()
+        .find_map(|dir| fs::read_to_string(dir.join(".git").join("HEAD")).ok())
+        .and_then(|head| branch_from_head(&head))
+}
+
+/// Extract the branch name from the content of `.git/HEAD`.
+///
+/// It is `ref: refs/heads/main` on a branch. Otherwise, the `HEAD` is detached
+/// and contains the hash of a commit, which is shortened like `git` does.
+fn branch_from_head(head: &str) -> Option<String> {
+    let head = head.trim();
+    if let Some(reference) = head.strip_prefix("ref: ") {
+        let branch = reference.strip_prefix("refs/heads/").unwrap_or(reference);
+        return Some(branch.to_string());
+    }
+    let hash = head.get(..7)?;
+    hash.chars()
+        .all(|c| c.is_ascii_hexdigit())
+        .then(|| hash.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn success_has_no_marker() {
+        assert_eq!(status_marker(Some(0), true), "");
+        assert_eq!(status_marker(None, true), "");
+        assert!(!render_prompt(None, Some(0)).contains('['));
+    }
+
+    #[test]
+    fn failure_has_marker() {
+        assert_eq!(status_marker(Some(1), false), "[1] ");
+        assert_eq!(status_marker(Some(127), true), "\x1b[31m[127]\x1b[0m ");
+        // Tests don't run in a terminal, so the marker isn't colored.
+        assert!(render_prompt(None, Some(2)).starts_with("[2] "));
+    }
+
+    #[test]
+    fn plain_prompt_is_kept() {
+        assert_eq!(expand_escapes("shell> ", Path::new("/tmp")), "shell> ");
+    }
+
+    #[test]
+    fn working_directory_is_expanded() {
+        assert_eq!(expand_escapes("\\w\\$ ", Path::new("/tmp")), "/tmp$ ");
+    }
+
+    #[test]
+    fn branch_is_read_from_head() {
+        assert_eq!(
+            branch_from_head("ref: refs/heads/main\n").as_deref(),
+            Some("main")
+        );
+        assert_eq!(
+            branch_from_head("ref: refs/heads/feature/prompt").as_deref(),
+            Some("feature/prompt")
+        );
+        assert_eq!(
+            branch_from_head("71a81bc0d6a1e6b2e9e0c5f3a0c2b1d4e5f60718\n").as_deref(),
+            Some("71a81bc")
+        );
+        assert_eq!(branch_from_head(""), None);
+    }
+
+    #[test]
+    fn git_branch_is_expanded() {
+        let repo = env::temp_dir().join(format!("rush-prompt-{}", std::process::id()));
+        let subdir = repo.join("src");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(&subdir).unwrap();
+        fs::write(repo.join(".git").join("HEAD"), "ref: refs/heads/main\n").unwrap();
+
+        let prompt = expand_escapes("\\g\\$ ", &subdir);
+        fs::remove_dir_all(&repo).unwrap();
+        assert_eq!(prompt, " (main)$ ");
+        assert_eq!(expand_escapes("\\g\\$ ", Path::new("/")), "$ ");
+    }
+
+    #[test]
+    fn unknown_escapes_are_kept() {
+        assert_eq!(expand_escapes("\\u \\", Path::new("/tmp")), "\\u \\");
+    }
+}
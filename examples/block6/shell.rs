@@ -0,0 +1,215 @@
+//! The state of the shell, which lives as long as the shell runs.
+//!
+//! Builtins like `cd -`, `alias` or `jobs` need to remember something between commands.
+//! Instead of passing each piece of state around on its own, the [`Shell`] holds all of it.
+
+use crate::{
+    builtins::{self, Aliases},
+    completion::Completions,
+    jobs::Jobs,
+    parser::{read_continued_lines, read_here_documents, Parser},
+    variables::Variables,
+};
+use std::{
+    path::{Path, PathBuf},
+    process::ExitStatus,
+};
+
+/// Name of the startup file in the user's home directory.
+const RC_FILE_NAME: &str = ".shellrc";
+
+pub struct Shell {
+    pub aliases: Aliases,
+    /// The completions registered with `complete`, which the line editor uses.
+    pub completions: Completions,
+    pub jobs: Jobs,
+    /// The directory before the last `cd`, for `cd -`.
+    pub previous_dir: Option<PathBuf>,
+    /// The directories saved by `pushd`, the most recent last.
+    pub dir_stack: Vec<PathBuf>,
+    /// The exit code of the last command, also known as `$?`.
+    /// `None` before the first command. A command killed by a signal has 128 + the signal number.
+    pub last_status: Option<i32>,
+    pub options: Options,
+    pub variables: Variables,
+}
+
+/// The options which can be turned on with `set -e` and off with `set +e`.
+#[derive(Default)]
+pub struct Options {
+    /// `-e`: exit as soon as a chain fails.
+    /// Failures which are handled with `||` don't count.
+    pub errexit: bool,
+    /// `-x`: print each command to stderr before it runs.
+    pub xtrace: bool,
+    /// `-o pipefail`: the status of a pipeline is the status of the last command which failed,
+    /// instead of the status of the last command.
+    pub pipefail: bool,
+    /// `-n`: print the parsed chains instead of running them.
+    /// Unlike the other options, it can only be turned on when the shell starts.
+    pub parse_only: bool,
+}
+
+impl Shell {
+    pub fn new() -> Self {
+        Self {
+            aliases: Aliases::new(),
+            completions: Completions::new(),
+            jobs: Jobs::new(),
+            previous_dir: None,
+            dir_stack: Vec::new(),
+            last_status: None,
+            options: Options::default(),
+            variables: Variables::new(),
+        }
+    }
+
+    /// Run all chains of `line` and return the exit status of the last one.
+    ///
+    /// Empty lines don't change the status, just like in other shells.
+    pub fn run_line(&mut self, line: &str) -> Option<ExitStatus> {
+        let chains = match Parser::new(line).with_aliases(&self.aliases).parse() {
+            Ok(chains) => chains,
+            Err(e) => {
+                eprintln!("Error: {e}");
+                // Like other shells, syntax errors have their own exit status.
+                let status = builtins::output(2, vec![]).status;
+                self.last_status = status.code();
+                return Some(status);
+            }
+        };
+        if self.options.parse_only {
+            for chain in chains {
+                println!("{chain:?}");
+            }
+            return None;
+        }
+        let mut status = None;
+        for chain in chains {
+            status = chain.run(self).or(status);
+            if let Some(status) = status.filter(|status| self.options.errexit && !status.success())
+            {
+                self.jobs.exit();
+                // Commands killed by a signal don't have an exit code.
+                std::process::exit(status.code().unwrap_or(1));
+            }
+        }
+        if let Some(status) = status {
+            self.last_status = status.code();
+        }
+        status
+    }
+
+    /// Run every line of the startup file, as if the user typed it.
+    /// This allows users to define aliases or `cd` into a directory on startup.
+    pub fn run_rc_file(&mut self) {
+        // The path can be overridden by setting the `SHELLRC_PATH` environment variable.
+        let rc_path = std::env::var("SHELLRC_PATH")
+            .map(PathBuf::from)
+            .ok()
+            .or_else(|| std::env::home_dir().map(|home| home.join(RC_FILE_NAME)));
+
+        // Most users don't have a startup file, so a missing one is not an error.
+        let Some(rc) = rc_path.and_then(|path| std::fs::read_to_string(path).ok()) else {
+            return;
+        };
+        let mut lines = rc.lines().map(String::from);
+        while let Some(line) = lines.next() {
+            let line = read_continued_lines(&line, || lines.next());
+            let line = read_here_documents(&line, || lines.next());
+            self.run_line(&line);
+        }
+    }
+
+    /// Run all lines of the script at `path` and return the exit code of the last command.
+    ///
+    /// The first line is skipped if it is a shebang like `#!/path/to/rush`.
+    /// Unlike an interactive shell, scripts don't read the startup file or use the history.
+    pub fn run_script(&mut self, path: &Path) -> i32 {
+        let script = match std::fs::read_to_string(path) {
+            Ok(script) => script,
+            Err(e) => {
+                eprintln!("Error: {}: {e}", path.display());
+                // Like other shells, exit with "command not found".
+                return 127;
+            }
+        };
+        let mut lines = script.lines().map(String::from).peekable();
+        lines.next_if(|line| line.starts_with("#!"));
+
+        let mut last_status = 0;
+        while let Some(line) = lines.next() {
+            let line = read_continued_lines(&line, || lines.next());
+            let line = read_here_documents(&line, || lines.next());
+            if let Some(status) = self.run_line(&line) {
+                // Commands killed by a signal don't have an exit code.
+                last_status = status.code().unwrap_or(1);
+            }
+        }
+        last_status
+    }
+}
+
+/// Increment `$SHLVL`, so commands can tell how deeply shells are nested.
+///
+/// The first shell has level 1. Like in bash, a missing or invalid level counts as 0.
+pub fn increment_shell_level() {
+    let level = std::env::var("SHLVL").ok();
+    std::env::set_var("SHLVL", next_shell_level(level.as_deref()).to_string());
+}
+
+fn next_shell_level(level: Option<&str>) -> i64 {
+    let level = level.and_then(|level| level.trim().parse::<i64>().ok());
+    level.unwrap_or(0).max(0).saturating_add(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn last_status_is_the_status_of_the_last_command() {
+        let mut shell = Shell::new();
+        assert_eq!(shell.last_status, None);
+        shell.run_line("false");
+        assert_eq!(shell.last_status, Some(1));
+        shell.run_line("false || true");
+        assert_eq!(shell.last_status, Some(0));
+        // Empty lines keep the status.
+        shell.run_line("false");
+        shell.run_line("");
+        assert_eq!(shell.last_status, Some(1));
+    }
+
+    #[test]
+    fn shell_level_is_incremented() {
+        assert_eq!(next_shell_level(Some("1")), 2);
+        assert_eq!(next_shell_level(Some(" 41 ")), 42);
+        assert_eq!(next_shell_level(None), 1);
+        assert_eq!(next_shell_level(Some("")), 1);
+        assert_eq!(next_shell_level(Some("nested")), 1);
+        assert_eq!(next_shell_level(Some("-3")), 1);
+    }
+
+    #[test]
+    fn aliases_are_kept_between_lines() {
+        let mut shell = Shell::new();
+        shell.run_line("alias yes=true");
+        assert_eq!(shell.aliases["yes"], "true");
+        shell.run_line("false; yes");
+        assert_eq!(shell.last_status, Some(0));
+    }
+
+    #[test]
+    fn failed_cd_keeps_the_directory_state() {
+        let cwd = std::env::current_dir().unwrap();
+        let mut shell = Shell::new();
+        shell.run_line("cd -");
+        assert_eq!(shell.last_status, Some(1));
+        shell.run_line("cd does-not-exist");
+        assert_eq!(shell.last_status, Some(1));
+        assert_eq!(shell.previous_dir, None);
+        assert!(shell.dir_stack.is_empty());
+        assert_eq!(std::env::current_dir().unwrap(), cwd);
+    }
+}
@@ -0,0 +1,106 @@
+//! Signal handling, so Ctrl-C stops the running command instead of the shell.
+//!
+//! By default, `SIGINT` terminates a process. A shell must survive it,
+//! but the command running in the foreground should still be interrupted.
+
+use std::{
+    process::ExitStatus,
+    sync::atomic::{AtomicI32, Ordering},
+};
+
+/// The PID of the command running in the foreground, or 0 if there is none.
+///
+/// A signal handler can run at any time, so it can only access
+/// global state which is safe to use from it, like atomics.
+static FOREGROUND_PID: AtomicI32 = AtomicI32::new(0);
+
+/// Marks a child process as running in the foreground while it is alive.
+/// `SIGINT` is forwarded to it until the guard is dropped.
+pub struct Foreground;
+
+impl Foreground {
+    pub fn new(pid: u32) -> Self {
+        FOREGROUND_PID.store(pid as i32, Ordering::SeqCst);
+        Self
+    }
+}
+
+impl Drop for Foreground {
+    fn drop(&mut self) {
+        FOREGROUND_PID.store(0, Ordering::SeqCst);
+    }
+}
+
+/// Install the `SIGINT` handler of the shell.
+#[cfg(unix)]
+pub fn install_handlers() {
+    extern "C" fn handle_sigint(_signal: libc::c_int) {
+        // `kill` is one of the few functions which are safe to call in a signal handler.
+        let pid = FOREGROUND_PID.load(Ordering::SeqCst);
+        if pid > 0 {
+            unsafe { libc::kill(pid, libc::SIGINT) };
+        }
+    }
+
+    // SAFETY: `sigaction` is zero-initialized as is expected by the C API.
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handle_sigint as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        // Continue reading from stdin after the signal was handled.
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(libc::SIGINT, &action, std::ptr::null_mut());
+    }
+}
+
+/// Ignore Ctrl-C in the shell.
+///
+/// On Windows, Ctrl-C is sent to every process attached to the console,
+/// so the command in the foreground receives it without our help.
+#[cfg(not(unix))]
+pub fn install_handlers() {
+    unsafe { libc::signal(libc::SIGINT, libc::SIG_IGN) };
+}
+
+/// Report a command which was killed by a signal, like bash: `Segmentation fault (core dumped)`.
+///
+/// Returns the status with the exit code 128 + the number of the signal, so `$?` is
+/// the same as in other shells. Like in bash, `SIGINT` and `SIGPIPE` are not reported,
+/// because they are expected after Ctrl-C or in pipelines like `yes | head`.
+#[cfg(unix)]
+pub fn report_signal(status: ExitStatus) -> ExitStatus {
+    use std::os::unix::process::ExitStatusExt;
+
+    let Some(signal) = status.signal() else {
+        return status;
+    };
+    if signal != libc::SIGINT && signal != libc::SIGPIPE {
+        let core_dumped = if status.core_dumped() {
+            " (core dumped)"
+        } else {
+            ""
+        };
+        eprintln!("{}{core_dumped}", signal_description(signal));
+    }
+    crate::builtins::output(128 + signal, Vec::new()).status
+}
+
+/// Commands can't be killed by a signal on Windows.
+#[cfg(not(unix))]
+pub fn report_signal(status: ExitStatus) -> ExitStatus {
+    status
+}
+
+/// The description of `signal`, like `Segmentation fault` for `SIGSEGV`.
+#[cfg(unix)]
+fn signal_description(signal: libc::c_int) -> String {
+    // SAFETY: `strsignal` returns a null-terminated string, or null on some platforms
+    // if the signal is unknown. The string is copied before the next call can change it.
+    let description = unsafe { libc::strsignal(signal) };
+    if description.is_null() {
+        return format!("Unknown signal {signal}");
+    }
+    unsafe { std::ffi::CStr::from_ptr(description) }
+        .to_string_lossy()
+        .into_owned()
+}
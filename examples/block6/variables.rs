@@ -0,0 +1,90 @@
+//! The variables of the shell, like `x` after `x=1` or `read x`.
+//!
+//! Like in other shells, commands only get the variables which are exported.
+//! Exported variables live in the environment of the shell process, which every
+//! command inherits. All other variables are only stored in [`Variables`].
+//! A variable is in one of the two places, never in both.
+
+use std::collections::HashMap;
+
+/// The variables of the shell which are not exported.
+#[derive(Default)]
+pub struct Variables {
+    local: HashMap<String, String>,
+}
+
+impl Variables {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The value of the variable `name`, whether it is exported or not.
+    ///
+    /// The environment comes first. While a command like `x=2 source script` runs,
+    /// its assignments are in the environment, even if `x` is also a shell variable.
+    pub fn get(&self, name: &str) -> Option<String> {
+        std::env::var(name)
+            .ok()
+            .or_else(|| self.local.get(name).cloned())
+    }
+
+    /// Set the variable `name` to `value`. An exported variable stays exported.
+    pub fn set(&mut self, name: &str, value: &str) {
+        if std::env::var_os(name).is_some() {
+            std::env::set_var(name, value);
+        } else {
+            self.local.insert(name.to_string(), value.to_string());
+        }
+    }
+
+    /// Export the variable `name`, so it is passed to commands.
+    /// With a value, the variable is also set to it.
+    ///
+    /// Exporting a variable which isn't set does nothing.
+    pub fn export(&mut self, name: &str, value: Option<&str>) {
+        let local = self.local.remove(name);
+        if let Some(value) = value.map(str::to_string).or(local) {
+            std::env::set_var(name, value);
+        }
+    }
+
+    /// The variables which are not exported, in no particular order.
+    pub fn local(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.local
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variables_are_not_exported() {
+        let mut variables = Variables::new();
+        variables.set("VARIABLES_TEST_LOCAL", "1");
+        assert_eq!(variables.get("VARIABLES_TEST_LOCAL").unwrap(), "1");
+        assert!(std::env::var_os("VARIABLES_TEST_LOCAL").is_none());
+    }
+
+    #[test]
+    fn exported_variables_stay_exported() {
+        let mut variables = Variables::new();
+        variables.set("VARIABLES_TEST_EXPORTED", "1");
+        variables.export("VARIABLES_TEST_EXPORTED", None);
+        assert_eq!(std::env::var("VARIABLES_TEST_EXPORTED").unwrap(), "1");
+        assert_eq!(variables.local().count(), 0);
+
+        variables.set("VARIABLES_TEST_EXPORTED", "2");
+        assert_eq!(std::env::var("VARIABLES_TEST_EXPORTED").unwrap(), "2");
+        assert_eq!(variables.local().count(), 0);
+    }
+
+    #[test]
+    fn exporting_an_unset_variable_does_nothing() {
+        let mut variables = Variables::new();
+        variables.export("VARIABLES_TEST_UNSET", None);
+        assert!(variables.get("VARIABLES_TEST_UNSET").is_none());
+    }
+}
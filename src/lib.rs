@@ -0,0 +1,16 @@
+//! The parser of the shell from block 5, as a library.
+//!
+//! Other programs and the tests can use it to split a line into commands,
+//! without running a shell:
+//!
+//! ```
+//! use rush::{parse, Element};
+//!
+//! let chains = parse("ls | wc -l; echo done").unwrap();
+//! assert_eq!(chains.len(), 2);
+//! assert_eq!(chains[0].elements[1], Element::Pipe);
+//! ```
+
+mod parser;
+
+pub use parser::{parse, Chain, Cmd, Element, ParseError, Parser};
@@ -48,12 +48,14 @@ impl Command {
 
     fn cd(&self) {
         match self.args.len() {
-            1 => {
-                let current = env::current_dir().expect("Unable to get working directory");
-                let new = current.join(self.args[0].clone());
-                env::set_current_dir(new).unwrap_or_else(|e| eprintln!("{e}"));
-            }
-            _ => eprintln!("Expected exactly one argument"),
+            0 => match env::home_dir() {
+                Some(home) => env::set_current_dir(home).unwrap_or_else(|e| eprintln!("{e}")),
+                None => eprintln!("Unable to find the home directory"),
+            },
+            // Relative paths are resolved by `set_current_dir` itself. This also works
+            // if the current directory was deleted, as long as the new path is absolute.
+            1 => env::set_current_dir(&self.args[0]).unwrap_or_else(|e| eprintln!("{e}")),
+            _ => eprintln!("Expected at most one argument"),
         }
     }
 }
@@ -0,0 +1,339 @@
+//! Parse a line into chains of commands, which are connected by `|`, `&&` and `||`.
+//!
+//! The commands borrow their binary and arguments from the line,
+//! so parsing doesn't allocate a `String` for each word.
+
+use std::fmt;
+
+/// Split `line` at `;` and parse each part into a [`Chain`].
+///
+/// So `;` binds weaker than `&&`, `||` and `|`, which connect the commands within a chain:
+/// `a; b && c` runs `a`, then `b && c`.
+/// Empty parts, like in `a ;; b` or after a trailing `;`, are skipped.
+/// If any part is malformed, the error is returned and none of the chains,
+/// so that a line is either run completely or not at all.
+///
+/// ```
+/// use rush::{parse, Cmd, Element, ParseError};
+///
+/// let chains = parse("cd src && ls -l;").unwrap();
+/// assert_eq!(chains.len(), 1);
+/// assert_eq!(
+///     chains[0].elements,
+///     [
+///         Element::Cmd(Cmd { binary: "cd", args: vec!["src"] }),
+///         Element::And,
+///         Element::Cmd(Cmd { binary: "ls", args: vec!["-l"] }),
+///     ]
+/// );
+/// assert_eq!(parse("echo |"), Err(ParseError::EmptyPipeline));
+/// ```
+pub fn parse(line: &str) -> Result<Vec<Chain<'_>>, ParseError> {
+    // For simplicity's sake, this uses the split function.
+    // A `;` in quotes ends the chain too, so the quote is reported as unterminated.
+    let mut chains = vec![];
+    // Blank chains, e.g. between `;;`, are skipped before they reach the parser.
+    for chain in line.split(';').filter(|chain| !chain.trim().is_empty()) {
+        chains.extend(Parser::new(chain).parse()?);
+    }
+    Ok(chains)
+}
+
+/// Why a line can't be parsed.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum ParseError {
+    /// `&&` or `||` without a command before or after it, like `&& ls`.
+    UnexpectedOperator(String),
+    /// A `'` or `"` which isn't closed, like `echo "hi`.
+    UnterminatedQuote(char),
+    /// `|` without a command before or after it, like `echo |`.
+    EmptyPipeline,
+}
+
+impl ParseError {
+    /// The error for `operator`, which is missing a command on one side.
+    fn missing_command(operator: &Element) -> Self {
+        match operator {
+            Element::And => Self::UnexpectedOperator("&&".to_string()),
+            Element::Or => Self::UnexpectedOperator("||".to_string()),
+            _ => Self::EmptyPipeline,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedOperator(operator) => {
+                write!(f, "syntax error near unexpected token `{operator}`")
+            }
+            Self::UnterminatedQuote(quote) => write!(f, "unterminated quote `{quote}`"),
+            Self::EmptyPipeline => write!(f, "syntax error: pipe without a command"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// The quote which is still open at the end of `text`, if any.
+fn unterminated_quote(text: &str) -> Option<char> {
+    let mut open = None;
+    for c in text.chars() {
+        match open {
+            None if c == '\'' || c == '"' => open = Some(c),
+            Some(quote) if c == quote => open = None,
+            _ => {}
+        }
+    }
+    open
+}
+
+/// A command with its arguments, e.g. `ls -l`.
+#[derive(PartialEq, Debug)]
+pub struct Cmd<'a> {
+    pub binary: &'a str,
+    pub args: Vec<&'a str>,
+}
+
+/// A part of a [`Chain`]: either a command or an operator between two commands.
+#[derive(PartialEq, Debug)]
+pub enum Element<'a> {
+    /// `|`
+    Pipe,
+    /// `&&`
+    And,
+    /// `||`
+    Or,
+    /// Command.
+    Cmd(Cmd<'a>),
+}
+
+impl Element<'_> {
+    fn parse_operator(token: &str) -> Option<Self> {
+        match token {
+            "|" => Some(Self::Pipe),
+            "&&" => Some(Self::And),
+            "||" => Some(Self::Or),
+            _ => None,
+        }
+    }
+
+    fn is_operator(token: &str) -> bool {
+        Self::parse_operator(token).is_some()
+    }
+}
+
+/// Commands and operators in the order they appear in the line, e.g. `ls | wc -l && echo ok`.
+#[derive(PartialEq, Debug)]
+pub struct Chain<'a> {
+    pub elements: Vec<Element<'a>>,
+}
+
+/// Parse the [`Element`]s of a single chain.
+///
+/// The words are separated by whitespace, so operators need spaces around them.
+pub struct Parser<'a> {
+    chain: &'a str,
+    current: usize,
+    tokens: Vec<&'a str>,
+}
+
+impl<'a> Parser<'a> {
+    /// Create a parser for `chain`, which must not contain a `;`.
+    pub fn new(chain: &'a str) -> Self {
+        Self {
+            chain,
+            tokens: chain.split_whitespace().collect(),
+            current: 0,
+        }
+    }
+
+    /// Parse the chain. Returns `None` if it doesn't contain any words.
+    pub fn parse(&mut self) -> Result<Option<Chain<'a>>, ParseError> {
+        if let Some(quote) = unterminated_quote(self.chain) {
+            return Err(ParseError::UnterminatedQuote(quote));
+        }
+        let mut elements = vec![];
+        while let Some(e) = self.parse_next() {
+            // Each operator needs a command before it.
+            let after_cmd = matches!(elements.last(), Some(Element::Cmd(_)));
+            if !matches!(e, Element::Cmd(_)) && !after_cmd {
+                return Err(ParseError::missing_command(&e));
+            }
+            elements.push(e);
+        }
+        match elements.last() {
+            None => Ok(None),
+            Some(Element::Cmd(_)) => Ok(Some(Chain { elements })),
+            // And after it.
+            Some(operator) => Err(ParseError::missing_command(operator)),
+        }
+    }
+
+    fn parse_next(&mut self) -> Option<Element<'a>> {
+        let next = self.tokens.get(self.current).copied();
+        next.map(|next| {
+            self.current += 1;
+            Element::parse_operator(next).unwrap_or_else(|| Element::Cmd(self.parse_cmd(next)))
+        })
+    }
+
+    fn parse_cmd(&mut self, binary: &'a str) -> Cmd<'a> {
+        let mut args = vec![];
+        while let Some(&token) = self.tokens.get(self.current) {
+            if Element::is_operator(token) {
+                // Found an operator, so the command is complete.
+                break;
+            }
+            args.push(token);
+            self.current += 1;
+        }
+        Cmd { binary, args }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cmd<'a>(binary: &'a str, args: &[&'a str]) -> Element<'a> {
+        Element::Cmd(Cmd {
+            binary,
+            args: args.to_vec(),
+        })
+    }
+
+    #[test]
+    fn no_chain_is_parsed_from_empty_line() {
+        assert_eq!(parse("").unwrap(), []);
+        assert_eq!(parse(" ; ;").unwrap(), []);
+        assert_eq!(parse(";;;").unwrap(), []);
+        assert_eq!(parse(" \t \n").unwrap(), []);
+    }
+
+    #[test]
+    fn cmd_with_args_is_parsed() {
+        assert_eq!(
+            parse("ls -l src").unwrap(),
+            [Chain {
+                elements: vec![cmd("ls", &["-l", "src"])]
+            }]
+        );
+    }
+
+    #[test]
+    fn semicolons_separate_chains() {
+        assert_eq!(
+            parse("ls; echo hello").unwrap(),
+            [
+                Chain {
+                    elements: vec![cmd("ls", &[])]
+                },
+                Chain {
+                    elements: vec![cmd("echo", &["hello"])]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_chains_are_skipped() {
+        assert_eq!(
+            parse("a ;; b").unwrap(),
+            [
+                Chain {
+                    elements: vec![cmd("a", &[])]
+                },
+                Chain {
+                    elements: vec![cmd("b", &[])]
+                },
+            ]
+        );
+        assert_eq!(
+            parse("; echo hi").unwrap(),
+            [Chain {
+                elements: vec![cmd("echo", &["hi"])]
+            }]
+        );
+    }
+
+    #[test]
+    fn semicolon_binds_weaker_than_operators() {
+        assert_eq!(
+            parse("a; b && c").unwrap(),
+            [
+                Chain {
+                    elements: vec![cmd("a", &[])]
+                },
+                Chain {
+                    elements: vec![cmd("b", &[]), Element::And, cmd("c", &[])]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn operators_are_parsed() {
+        assert_eq!(
+            parse("ls | wc -l && echo ok || echo failed").unwrap(),
+            [Chain {
+                elements: vec![
+                    cmd("ls", &[]),
+                    Element::Pipe,
+                    cmd("wc", &["-l"]),
+                    Element::And,
+                    cmd("echo", &["ok"]),
+                    Element::Or,
+                    cmd("echo", &["failed"]),
+                ]
+            }]
+        );
+    }
+
+    #[test]
+    fn cmd_borrows_from_line() {
+        let line = String::from("echo hello");
+        let chains = parse(&line).unwrap();
+        let Element::Cmd(cmd) = &chains[0].elements[0] else {
+            panic!("expected a command");
+        };
+        let range = line.as_bytes().as_ptr_range();
+        assert!(range.contains(&cmd.binary.as_ptr()));
+        assert!(range.contains(&cmd.args[0].as_ptr()));
+    }
+
+    #[test]
+    fn operators_without_commands_are_errors() {
+        let unexpected = |operator: &str| Err(ParseError::UnexpectedOperator(operator.into()));
+        assert_eq!(parse("&& ls"), unexpected("&&"));
+        assert_eq!(parse("ls ||"), unexpected("||"));
+        assert_eq!(parse("ls && || echo"), unexpected("||"));
+        assert_eq!(parse("ls; && echo"), unexpected("&&"));
+    }
+
+    #[test]
+    fn pipes_without_commands_are_errors() {
+        assert_eq!(parse("echo |"), Err(ParseError::EmptyPipeline));
+        assert_eq!(parse("| wc"), Err(ParseError::EmptyPipeline));
+        assert_eq!(parse("ls | | wc"), Err(ParseError::EmptyPipeline));
+        assert_eq!(parse("ls && | wc"), Err(ParseError::EmptyPipeline));
+    }
+
+    #[test]
+    fn unterminated_quotes_are_errors() {
+        assert_eq!(parse("echo \"hi"), Err(ParseError::UnterminatedQuote('"')));
+        assert_eq!(parse("echo it's"), Err(ParseError::UnterminatedQuote('\'')));
+        assert_eq!(
+            parse("echo \"it's\""),
+            Ok(vec![Chain {
+                elements: vec![cmd("echo", &["\"it's\""])]
+            }])
+        );
+    }
+
+    #[test]
+    fn errors_are_displayed_like_in_other_shells() {
+        let error = parse("&& ls").unwrap_err();
+        assert_eq!(error.to_string(), "syntax error near unexpected token `&&`");
+    }
+}
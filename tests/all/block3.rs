@@ -1,6 +1,6 @@
 use std::time::Duration;
 
-use crate::utils::ShellRunner;
+use crate::utils::{AssertOutput, ShellRunner};
 
 const SHELL_TIMEOUT: Duration = Duration::from_secs(5);
 
@@ -51,11 +51,10 @@ fn invalid_cd_doesnt_change_dir() {
 
 #[test]
 fn supports_exit_builtin() {
-    let output = ShellRunner::new()
+    ShellRunner::new()
         .with_stdin("exit 1")
         .example("block3")
         .kill_after(SHELL_TIMEOUT)
-        .run();
-
-    assert_eq!(output.status.code(), Some(1));
+        .run()
+        .assert_exit(1);
 }
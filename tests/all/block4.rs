@@ -1,28 +1,19 @@
-use std::time::{SystemTime, UNIX_EPOCH};
-use std::{env, fs, path::PathBuf, process, time::Duration};
+use std::{
+    fs,
+    io::{BufRead, BufReader},
+    time::{Duration, Instant},
+};
 
-use crate::utils::ShellRunner;
+use crate::utils::{generate_temp_file_name, ShellRunner};
 
 const SHELL_TIMEOUT: Duration = Duration::from_secs(10);
 
-fn generate_temp_file_name() -> PathBuf {
-    let temp_dir = env::temp_dir();
-    let pid = process::id();
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("Time went backwards")
-        .as_nanos();
-    let file_name = format!("temp_file_{}_{}", pid, timestamp);
-    temp_dir.join(file_name)
-}
-
 #[test]
 fn test_history() {
     // Random path to history
     let history_path = generate_temp_file_name();
-    unsafe { std::env::set_var("HISTORY_PATH", &history_path) };
-
     ShellRunner::new()
+        .with_env("HISTORY_PATH", &history_path)
         .with_stdin("echo 1\necho 2\nhistory")
         .example("block4")
         // This test takes longer to complete sometimes so we increase the
@@ -30,6 +21,60 @@ fn test_history() {
         .kill_after(SHELL_TIMEOUT)
         .run();
 
-    let history_contents = fs::read_to_string(history_path).unwrap();
+    let history_contents = fs::read_to_string(&history_path).unwrap();
+    fs::remove_file(history_path).unwrap();
     assert!(history_contents.starts_with("echo 1\necho 2\nhistory\n"))
 }
+
+#[test]
+fn blank_lines_are_not_added_to_the_history() {
+    let history_path = generate_temp_file_name();
+    ShellRunner::new()
+        .with_env("HISTORY_PATH", &history_path)
+        .with_stdin("\necho 1\n   \n\necho 2\n\t\n")
+        .example("block4")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let history_contents = fs::read_to_string(&history_path).unwrap();
+    fs::remove_file(history_path).unwrap();
+    assert_eq!(history_contents, "echo 1\necho 2\n");
+}
+
+#[test]
+fn cd_without_arguments_goes_home() {
+    let output = ShellRunner::new()
+        .with_stdin("cd\npwd\n")
+        .example("block4")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let home = std::env::home_dir().unwrap();
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, format!("{}\n", home.display()));
+}
+
+#[test]
+fn output_of_external_commands_appears_while_they_run() {
+    let script_path = generate_temp_file_name();
+    fs::write(&script_path, "echo first\nsleep 1\necho second\n").unwrap();
+    let stdin = format!("sh {}\nexit\n", script_path.display());
+    let mut child = ShellRunner::new()
+        .with_stdin(&stdin)
+        .example("block4")
+        .spawn();
+
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut first = String::new();
+    stdout.read_line(&mut first).unwrap();
+    let first_seen = Instant::now();
+    let mut second = String::new();
+    stdout.read_line(&mut second).unwrap();
+    let between = first_seen.elapsed();
+    child.wait().unwrap();
+    fs::remove_file(script_path).unwrap();
+
+    assert_eq!((first.as_str(), second.as_str()), ("first\n", "second\n"));
+    // If the output was collected until the command exits, both lines would arrive together.
+    assert!(between >= Duration::from_millis(500), "{between:?}");
+}
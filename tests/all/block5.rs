@@ -15,3 +15,86 @@ fn test_pipes_evaluation() {
     let stdout_str = String::from_utf8(output.stdout).unwrap();
     assert_eq!(stdout_str.trim_start(), "6\n");
 }
+
+#[test]
+fn test_three_stage_pipeline() {
+    let output = ShellRunner::new()
+        .with_stdin("printf a\\nb\\nc\\n | grep -v b | wc -l\n")
+        .example("block5")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str.trim_start(), "2\n");
+}
+
+#[test]
+fn test_pipeline_with_missing_first_command() {
+    let output = ShellRunner::new()
+        .with_stdin("rush-missing-command | wc -l\nexit\n")
+        .example("block5")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    // The shell exits by itself instead of being killed after the timeout.
+    assert!(output.status.success());
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str.trim_start(), "0\n");
+    let stderr_str = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr_str.contains("rush-missing-command: command not found"));
+}
+
+#[test]
+fn test_malformed_line_is_reported() {
+    let output = ShellRunner::new()
+        .with_stdin("echo hello && ; echo |\necho next\n")
+        .example("block5")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    // Nothing of the malformed line runs.
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "next\n");
+    let stderr_str = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr_str.contains("Error: syntax error near unexpected token `&&`"));
+}
+
+#[test]
+fn test_empty_chains_are_skipped() {
+    let output = ShellRunner::new()
+        .with_stdin("; echo hi\necho a ;; echo b\n")
+        .example("block5")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "hi\na\nb\n");
+}
+
+#[test]
+fn test_blank_lines_are_no_ops() {
+    let output = ShellRunner::new()
+        .with_stdin(";;;\n  ;  \n   \necho done\n")
+        .example("block5")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "done\n");
+    let stderr_str = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr_str, "");
+}
+
+#[test]
+fn test_large_output_streams_through_the_pipeline() {
+    let output = ShellRunner::new()
+        .with_stdin("head -c 50000000 /dev/zero | wc -c\nyes | head -n 2\n")
+        .example("block5")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    // `yes` never stops on its own. The shell only gets past it if `yes`
+    // writes into a pipe which is closed once `head` is done.
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str.trim_start(), "50000000\ny\ny\n");
+}
@@ -0,0 +1,1621 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader},
+    time::{Duration, Instant},
+};
+
+use crate::utils::{generate_temp_file_name, AssertOutput, ShellRunner};
+
+const SHELL_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[test]
+fn operators_dont_need_whitespace() {
+    let output = ShellRunner::new()
+        .with_stdin("echo hello|wc -c\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str.trim_start(), "6\n");
+}
+
+#[test]
+fn repeated_bang_bang_reruns_the_expanded_command() {
+    let history_path = generate_temp_file_name();
+    let output = ShellRunner::new()
+        .with_env("HISTORY_PATH", &history_path)
+        .with_stdin("echo hi\n!!\n!!\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let history_contents = fs::read_to_string(&history_path).unwrap();
+    fs::remove_file(history_path).unwrap();
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "hi\nhi\nhi\n");
+    // Repeated commands are only stored once.
+    assert_eq!(history_contents, "echo hi\n");
+}
+
+#[test]
+fn history_size_limits_the_history_file() {
+    let history_path = generate_temp_file_name();
+    ShellRunner::new()
+        .with_env("HISTORY_PATH", &history_path)
+        .with_env("HISTSIZE", "2")
+        .with_stdin("echo one\necho two\necho three\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let history_contents = fs::read_to_string(&history_path).unwrap();
+    fs::remove_file(history_path).unwrap();
+    assert_eq!(history_contents, "echo two\necho three\n");
+}
+
+#[test]
+fn commands_run_even_if_the_history_cannot_be_written() {
+    let history_path = generate_temp_file_name().join("history");
+    let output = ShellRunner::new()
+        .with_env("HISTORY_PATH", &history_path)
+        .with_stdin("echo hi\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    output.assert_exit(0);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "hi\n");
+    let stderr_str = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr_str.starts_with("Error: "), "{stderr_str}");
+}
+
+#[test]
+fn bang_bang_can_be_part_of_a_command() {
+    let output = ShellRunner::new()
+        .with_stdin("!!\necho hi\nenv !! there\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "hi\nhi there\n");
+    // Without a previous command, there is nothing to expand.
+    let stderr_str = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr_str.contains("!!: event not found"), "{stderr_str}");
+}
+
+#[test]
+fn bang_number_reruns_a_command_from_the_history() {
+    let history_path = generate_temp_file_name();
+    fs::write(&history_path, "echo one\necho two\n").unwrap();
+    let output = ShellRunner::new()
+        .with_env("HISTORY_PATH", &history_path)
+        .with_stdin("!1\n!-1\n!9 && echo unreachable\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    fs::remove_file(history_path).unwrap();
+    // `!1` itself is added to the history as `echo one`, so `!-1` runs it again.
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "one\none\n");
+    let stderr_str = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr_str.contains("!9: event not found"), "{stderr_str}");
+}
+
+#[test]
+fn trailing_backslash_continues_the_line() {
+    let output = ShellRunner::new()
+        .with_stdin("echo a\\\nb \\\nc\necho d\\\\\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "ab c\nd\\\n");
+}
+
+#[test]
+fn unclosed_quotes_continue_on_the_next_line() {
+    let output = ShellRunner::new()
+        .with_stdin("echo \"a\nb\" | wc -l\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str.trim_start(), "2\n");
+}
+
+#[test]
+fn export_lists_the_environment() {
+    let output = ShellRunner::new()
+        .with_env("RUSH_EXPORTED", "it's")
+        .with_stdin("export RUSH_SET=1\nexport | grep RUSH_\nexport -p | grep RUSH_SET\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    // The variables are sorted by name and quoted.
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout_str,
+        "export RUSH_EXPORTED='it'\\''s'\nexport RUSH_SET='1'\nexport RUSH_SET='1'\n"
+    );
+}
+
+#[test]
+fn export_sets_several_variables() {
+    let output = ShellRunner::new()
+        .with_stdin("export A=1 B=2 && echo $A$B\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "12\n");
+}
+
+#[test]
+fn export_reports_invalid_names_and_sets_the_others() {
+    let output = ShellRunner::new()
+        .with_stdin("export 1X=1 RUSH_B=2 RUSH_BARE || echo $RUSH_B\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "2\n");
+    let stderr_str = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr_str.contains("export: `1X=1': not a valid identifier"));
+    assert!(!stderr_str.contains("RUSH_BARE"));
+}
+
+#[test]
+fn printenv_prints_all_variables() {
+    let output = ShellRunner::new()
+        .with_stdin("printenv\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    let path = format!("PATH={}", std::env::var("PATH").unwrap());
+    assert!(stdout_str.lines().any(|line| line == path));
+}
+
+#[test]
+fn printenv_prints_single_variables() {
+    let output = ShellRunner::new()
+        .with_env("RUSH_PRINTED", "value")
+        .with_stdin("printenv RUSH_PRINTED\nprintenv RUSH_UNSET || echo unset\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "value\nunset\n");
+}
+
+#[test]
+fn set_toggles_shell_options() {
+    let output = ShellRunner::new()
+        .with_stdin(
+            "set -x; echo hi\nset +x; echo quiet\nset\nset -y || set -e; false\necho unreachable\n",
+        )
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    output.assert_exit(1);
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout_str,
+        "hi\nquiet\nerrexit\toff\nxtrace\toff\npipefail\toff\n"
+    );
+    let stderr_str = String::from_utf8(output.stderr).unwrap();
+    let traces: Vec<_> = stderr_str
+        .lines()
+        .filter(|line| line.starts_with('+'))
+        .collect();
+    assert_eq!(traces, ["+ echo hi", "+ set +x"]);
+}
+
+#[test]
+fn pipefail_uses_the_status_of_the_last_failed_command() {
+    let output = ShellRunner::new()
+        .with_stdin(concat!(
+            "false | true && echo default\n",
+            "set -o pipefail; false | true || echo pipefail\n",
+            "set +o pipefail; false | true && echo off\n",
+            "set -o nonsense || echo failed\n",
+            "set -o pipefail; sh -c 'exit 2' | sh -c 'exit 3' | true\n",
+        ))
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    // The shell exits with the status of the last pipeline, the rightmost failure.
+    output.assert_exit(3);
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "default\npipefail\noff\nfailed\n");
+}
+
+#[test]
+fn xtrace_prints_each_command_of_pipelines_and_chains() {
+    let output = ShellRunner::new()
+        .with_stdin("set -x\nx='a b'; echo $x | tr a-z A-Z\nfalse && echo skipped || echo \"$x\"\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "A B\na b\n");
+    let stderr_str = String::from_utf8(output.stderr).unwrap();
+    let traces: Vec<_> = stderr_str
+        .lines()
+        .filter(|line| line.starts_with('+'))
+        .collect();
+    assert_eq!(
+        traces,
+        [
+            "+ x='a b'",
+            "+ echo a b",
+            "+ tr a-z A-Z",
+            "+ false",
+            "+ echo 'a b'"
+        ]
+    );
+}
+
+#[test]
+fn aliases_from_rc_file_are_available() {
+    let rc_path = generate_temp_file_name();
+    fs::write(&rc_path, "alias greet=echo\n").unwrap();
+    let output = ShellRunner::new()
+        .with_env("SHELLRC_PATH", &rc_path)
+        .with_stdin("greet hello\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "hello\n");
+}
+
+#[test]
+fn no_rc_skips_the_rc_file() {
+    let rc_path = generate_temp_file_name();
+    fs::write(&rc_path, "alias greet=echo\n").unwrap();
+    let output = ShellRunner::new()
+        .with_env("SHELLRC_PATH", &rc_path)
+        .with_args(&["--no-rc"])
+        .with_stdin("greet hello || echo no alias\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    fs::remove_file(rc_path).unwrap();
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "no alias\n");
+}
+
+#[test]
+fn complete_lists_registered_completions() {
+    let output = ShellRunner::new()
+        .with_stdin("complete -c gitcmd sub1 sub2\ncomplete\ncomplete -x || echo failed\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "complete -c gitcmd sub1 sub2\nfailed\n");
+}
+
+#[test]
+fn custom_prompt_is_not_printed_to_pipes() {
+    let output = ShellRunner::new()
+        .with_env("PS1", "\\w\\$ ")
+        .with_stdin("echo hello\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "hello\n");
+}
+
+#[test]
+fn exec_redirects_output_of_the_shell() {
+    let out_path = generate_temp_file_name();
+    let stdin = format!("exec > {}\necho hi\n", out_path.display());
+
+    let output = ShellRunner::new()
+        .with_stdin(&stdin)
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+    assert_eq!(fs::read_to_string(out_path).unwrap(), "hi\n");
+}
+
+#[test]
+fn builtin_sort_reads_from_pipe() {
+    let output = ShellRunner::new()
+        .with_stdin("printf 'b\\na\\nc\\n' | sort\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "a\nb\nc\n");
+}
+
+#[test]
+fn escaped_bang_is_printed_literally() {
+    let output = ShellRunner::new()
+        .with_stdin("echo \\!foo\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "!foo\n"); // Nothing was expanded, so the line isn't shown again.
+    let stderr_str = String::from_utf8(output.stderr).unwrap();
+    assert!(!stderr_str.contains("echo !foo"), "{stderr_str}");
+}
+
+#[test]
+fn builtin_head_limits_lines() {
+    let output = ShellRunner::new()
+        .with_stdin("seq 100 | head -n 3\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "1\n2\n3\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn sigint_interrupts_the_command_but_not_the_shell() {
+    // The background subshell sends `SIGINT` to our shell (`$PPID` of `sh`) while `sleep` runs.
+    // The shell forwards it to `sleep` and continues with the next line.
+    let output = ShellRunner::new()
+        .with_stdin(
+            "sh -c '(sleep 0.5; kill -INT $PPID) >/dev/null 2>&1 & exec sleep 10'\necho alive\n",
+        )
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "alive\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn commands_killed_by_a_signal_are_reported() {
+    let output = ShellRunner::new()
+        .with_stdin(
+            "yes | head -n 1\nsh -c 'kill -SEGV $$' || echo failed\nsh -c 'kill -TERM $$'\n",
+        )
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    // The status is 128 + 15 for `SIGTERM`.
+    output.assert_exit(143);
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "y\nfailed\n");
+    // `yes` is killed by `SIGPIPE`, which isn't reported.
+    let stderr_str = String::from_utf8(output.stderr).unwrap();
+    let messages: Vec<_> = stderr_str
+        .lines()
+        .map(|line| line.trim_end_matches(" (core dumped)"))
+        .collect();
+    assert_eq!(messages, ["Segmentation fault", "Terminated"]);
+}
+
+#[test]
+fn assignments_are_expanded_after_the_line_is_parsed() {
+    // Just like in bash, `$RUSH_INLINE` is expanded before it is assigned.
+    let output = ShellRunner::new()
+        .with_stdin("RUSH_INLINE=bar echo x$RUSH_INLINE\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "x\n");
+}
+
+#[test]
+fn assignments_are_passed_to_external_commands() {
+    let output = ShellRunner::new()
+        .with_stdin("RUSH_INLINE=bar sh -c 'echo $RUSH_INLINE'\necho x$RUSH_INLINE\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "bar\nx\n");
+}
+
+#[test]
+fn assignments_without_a_command_are_kept() {
+    let output = ShellRunner::new()
+        .with_stdin("RUSH_INLINE=bar\necho $RUSH_INLINE\nsh -c 'echo x$RUSH_INLINE'\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    output.assert_exit(0);
+    // The variable is kept in the shell, but it isn't exported.
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "bar\nx\n");
+}
+
+#[test]
+fn assignments_before_regular_builtins_are_temporary() {
+    let output = ShellRunner::new()
+        .with_stdin("RUSH_INLINE=bar sort < /dev/null\necho x$RUSH_INLINE\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "x\n");
+}
+
+#[test]
+fn assignments_before_special_builtins_are_kept() {
+    let output = ShellRunner::new()
+        .with_stdin("RUSH_INLINE=bar exec\necho x$RUSH_INLINE\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "xbar\n");
+}
+
+#[test]
+fn read_takes_the_next_line_of_the_shell_input() {
+    let output = ShellRunner::new()
+        .with_stdin("read X; echo $X\nhello world\necho after\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "hello world\nafter\n");
+}
+
+#[test]
+fn read_splits_the_line_into_variables() {
+    let output = ShellRunner::new()
+        .with_stdin("echo a b c | read X Y; echo $Y-$X\nread X < /dev/null || echo eof\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "b c-a\neof\n");
+}
+
+#[test]
+fn variables_are_only_passed_to_commands_once_exported() {
+    let output = ShellRunner::new()
+        .with_stdin(
+            "read X\nhello\nY=1\nprintenv X Y || echo unset\necho \"$(echo $X$Y)\"\nexport X Y; printenv X Y\n",
+        )
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "unset\nhello1\nhello\n1\n");
+}
+
+#[test]
+fn wait_blocks_until_jobs_are_done() {
+    let temp_file = generate_temp_file_name();
+    let stdin = format!(
+        "sh -c 'sleep 0.2; echo done > {}' &\nsh -c 'sleep 0.2; exit 3' &\nwait %2\necho $PIPESTATUS\nwait\ncat {0}\nwait %1 || echo gone\n",
+        temp_file.display()
+    );
+    let output = ShellRunner::new()
+        .with_stdin(&stdin)
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    fs::remove_file(temp_file).unwrap();
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "3\ndone\ngone\n");
+}
+
+#[test]
+fn background_jobs_dont_block_the_shell() {
+    // Redirect the output of the job, otherwise we would wait
+    // until it closes the stdout of the shell.
+    let output = ShellRunner::new()
+        .with_stdin("sleep 10 > /dev/null &\necho done\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "done\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn huponexit_hangs_up_background_jobs() {
+    let temp_file = generate_temp_file_name();
+    let job = format!(
+        "sh -c 'trap \"echo hup > {}; exit\" HUP; sleep 10 & wait' > /dev/null 2> /dev/null &",
+        temp_file.display()
+    );
+    // Give `sh` some time to set up the trap before the shell exits.
+    let stdin = format!("shopt -s huponexit\n{job}\nsleep 0.5\nexit\n");
+    ShellRunner::new()
+        .with_stdin(&stdin)
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    assert_eq!(fs::read_to_string(&temp_file).unwrap(), "hup\n");
+    fs::remove_file(temp_file).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn background_jobs_survive_exit_by_default() {
+    let temp_file = generate_temp_file_name();
+    let job = format!(
+        "sh -c 'trap \"echo hup > {}; exit\" HUP; sleep 10 & wait' > /dev/null 2> /dev/null &",
+        temp_file.display()
+    );
+    let stdin = format!("{job}\nsleep 0.5\nexit\n");
+    ShellRunner::new()
+        .with_stdin(&stdin)
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    assert!(!temp_file.exists());
+}
+
+#[test]
+fn source_runs_commands_in_the_current_shell() {
+    let script_path = generate_temp_file_name();
+    fs::write(&script_path, "cd examples\n").unwrap();
+    let stdin = format!("source {}\npwd\n", script_path.display());
+
+    let output = ShellRunner::new()
+        .with_stdin(&stdin)
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout_str.ends_with("/examples\n"), "{stdout_str}");
+    fs::remove_file(script_path).unwrap();
+}
+
+#[test]
+fn sourcing_a_missing_file_is_an_error() {
+    let stdin = format!(". {} || echo failed\n", generate_temp_file_name().display());
+
+    let output = ShellRunner::new()
+        .with_stdin(&stdin)
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "failed\n");
+}
+
+#[test]
+fn version_is_printed() {
+    for flag in ["--version", "-V"] {
+        let output = ShellRunner::new()
+            .with_args(&[flag])
+            .example("block6")
+            .kill_after(SHELL_TIMEOUT)
+            .run();
+
+        output.assert_exit(0);
+        let stdout_str = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(stdout_str, format!("rush {}\n", env!("CARGO_PKG_VERSION")));
+    }
+}
+
+#[test]
+fn parse_only_prints_the_chains_without_running_them() {
+    let output = ShellRunner::new()
+        .with_args(&["--parse-only"])
+        .with_stdin("ls -l && echo hi\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout_str.starts_with("Chain { elements: ["),
+        "{stdout_str}"
+    );
+    assert!(
+        stdout_str.contains("binary: \"ls\", args: [\"-l\"]"),
+        "{stdout_str}"
+    );
+    assert!(stdout_str.contains("}), And, Pipeline("), "{stdout_str}");
+    assert!(!stdout_str.lines().any(|line| line == "hi"), "{stdout_str}");
+}
+
+#[test]
+fn errexit_stops_at_the_first_failure() {
+    let output = ShellRunner::new()
+        .with_args(&["-e"])
+        .with_stdin("false || echo handled\nsh -c 'exit 3'\necho should-not-print\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    output.assert_exit(3);
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "handled\n");
+}
+
+#[test]
+fn exit_without_argument_uses_the_last_status() {
+    for stdin in ["false; exit\necho unreachable\n", "false || exit\n"] {
+        let output = ShellRunner::new()
+            .with_stdin(stdin)
+            .example("block6")
+            .kill_after(SHELL_TIMEOUT)
+            .run();
+
+        output.assert_exit(1);
+        assert!(output.stdout.is_empty());
+    }
+}
+
+#[test]
+fn exit_with_invalid_status_is_an_error() {
+    let output = ShellRunner::new()
+        .with_stdin("exit abc\necho unreachable\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    output.assert_exit(2);
+    assert!(output.stdout.is_empty());
+    let stderr_str = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr_str.contains("numeric argument required"),
+        "{stderr_str}"
+    );
+}
+
+#[test]
+fn exit_with_too_many_arguments_doesnt_exit() {
+    let output = ShellRunner::new()
+        .with_stdin("exit 1 2 || echo failed\nexit 3\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    output.assert_exit(3);
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "failed\n");
+    let stderr_str = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr_str.contains("too many arguments"), "{stderr_str}");
+}
+
+#[test]
+fn end_of_input_exits_with_the_last_status() {
+    let output = ShellRunner::new()
+        .with_stdin("false\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+    output.assert_exit(1);
+
+    let output = ShellRunner::new()
+        .with_stdin("false\ntrue\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+    output.assert_exit(0);
+}
+
+#[test]
+fn cd_dash_returns_to_the_previous_directory() {
+    let output = ShellRunner::new()
+        .with_stdin("cd examples; cd -; pwd\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    // `cd -` prints the directory it changes into.
+    let cwd = std::env::current_dir().unwrap();
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, format!("{0}\n{0}\n", cwd.display()));
+}
+
+#[test]
+fn cd_updates_pwd_and_oldpwd() {
+    let output = ShellRunner::new()
+        .with_stdin("cd examples; printenv PWD OLDPWD\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let cwd = std::env::current_dir().unwrap();
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout_str,
+        format!("{}\n{}\n", cwd.join("examples").display(), cwd.display())
+    );
+}
+
+#[test]
+fn cd_searches_cdpath() {
+    let cwd = std::env::current_dir().unwrap();
+    let output = ShellRunner::new()
+        .with_env("CDPATH", cwd.join("examples"))
+        .with_stdin("cd block6; pwd\ncd ./block6 || echo failed\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    // The directory found in `$CDPATH` is printed. `./` bypasses `$CDPATH`.
+    let dir = cwd.join("examples").join("block6");
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, format!("{0}\n{0}\nfailed\n", dir.display()));
+}
+
+#[test]
+fn cd_dash_without_previous_directory_is_an_error() {
+    let output = ShellRunner::new()
+        .with_stdin("cd - || echo failed\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "failed\n");
+}
+
+#[test]
+fn cd_double_dash_changes_into_a_directory_named_dash() {
+    let parent = generate_temp_file_name();
+    std::fs::create_dir_all(parent.join("-")).unwrap();
+    let output = ShellRunner::new()
+        .with_stdin(&format!("cd {}; cd -- -; pwd\n", parent.display()))
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+    std::fs::remove_dir_all(&parent).unwrap();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, format!("{}\n", parent.join("-").display()));
+}
+
+#[test]
+fn cd_into_a_file_is_an_error() {
+    let output = ShellRunner::new()
+        .with_stdin("cd Cargo.toml\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stderr_str = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr_str.contains("cd: not a directory: Cargo.toml"),
+        "{stderr_str}"
+    );
+}
+
+#[test]
+fn cd_into_a_missing_directory_is_an_error() {
+    let output = ShellRunner::new()
+        .with_stdin("cd rush-missing-dir\ncd -P rush-missing-dir\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stderr_str = String::from_utf8(output.stderr).unwrap();
+    let message = "cd: no such file or directory: rush-missing-dir";
+    assert_eq!(stderr_str.matches(message).count(), 2, "{stderr_str}");
+}
+
+#[cfg(unix)]
+#[test]
+fn disowned_jobs_are_not_listed_or_hung_up() {
+    let temp_file = generate_temp_file_name();
+    let job = format!(
+        "sh -c 'sleep 0.5; echo alive > {}' > /dev/null 2> /dev/null &",
+        temp_file.display()
+    );
+    let stdin = format!("shopt -s huponexit\n{job}\ndisown\njobs\nexit\n");
+    let output = ShellRunner::new()
+        .with_stdin(&stdin)
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+    // The job kept running after the shell exited. Give it time to finish.
+    let start = std::time::Instant::now();
+    let read = || fs::read_to_string(&temp_file).unwrap_or_default();
+    while read() != "alive\n" && start.elapsed() < SHELL_TIMEOUT {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(read(), "alive\n");
+    fs::remove_file(temp_file).unwrap();
+}
+
+#[test]
+fn builtin_tr_translates_and_deletes() {
+    let output = ShellRunner::new()
+        .with_stdin("echo hello | tr a-z A-Z\necho h.e.l | tr -d .\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "HELLO\nhel\n");
+}
+
+#[test]
+fn cd_without_arguments_goes_home() {
+    let output = ShellRunner::new()
+        .with_stdin("cd\npwd\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let home = std::env::home_dir().unwrap();
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, format!("{}\n", home.display()));
+}
+
+#[cfg(unix)]
+#[test]
+fn cd_physical_resolves_symbolic_links() {
+    let temp_dir = generate_temp_file_name();
+    let target = temp_dir.join("target");
+    let link = temp_dir.join("link");
+    fs::create_dir_all(&target).unwrap();
+    std::os::unix::fs::symlink(&target, &link).unwrap();
+    let stdin = format!("cd -L {0}\npwd -L\ncd -P {0}\npwd -L\n", link.display());
+
+    let output = ShellRunner::new()
+        .with_stdin(&stdin)
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    let resolved = target.canonicalize().unwrap();
+    assert_eq!(
+        stdout_str,
+        format!("{}\n{}\n", link.display(), resolved.display())
+    );
+    fs::remove_dir_all(temp_dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn script_with_shebang_runs_in_the_shell() {
+    use std::os::unix::fs::PermissionsExt;
+    use std::process::Command;
+
+    // Make sure the example binary exists. It is in `target/debug/examples`,
+    // next to the `deps` directory which contains this test binary.
+    let status = Command::new("cargo")
+        .args(["build", "--example", "block6"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let shell = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .and_then(|deps| deps.parent())
+        .unwrap()
+        .join("examples")
+        .join("block6");
+
+    let script_path = generate_temp_file_name();
+    let script = format!("#!{}\necho from script | tr a-z A-Z\n", shell.display());
+    fs::write(&script_path, script).unwrap();
+    fs::set_permissions(&script_path, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let output = Command::new(&script_path).output().unwrap();
+    fs::remove_file(script_path).unwrap();
+    output.assert_exit(0);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "FROM SCRIPT\n");
+}
+
+#[test]
+fn scripts_can_have_comments_and_blank_lines() {
+    let script_path = generate_temp_file_name();
+    let script = "#!/usr/bin/env rush\n# Say hello\n\necho hello # world\n  # indented\necho bye\n";
+    fs::write(&script_path, script).unwrap();
+
+    let output = ShellRunner::new()
+        .with_args(&[script_path.to_str().unwrap()])
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    fs::remove_file(script_path).unwrap();
+    output.assert_exit(0);
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "hello\nbye\n");
+}
+
+#[test]
+fn overlong_lines_are_rejected() {
+    // Longer than the limit of 1 MiB, without a newline in between.
+    let stdin = format!("echo {}\necho still here\n", "a".repeat(2 * 1024 * 1024));
+    let output = ShellRunner::new()
+        .with_stdin(&stdin)
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    output.assert_exit(0);
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "still here\n");
+    let stderr_str = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr_str.contains("line too long"), "{stderr_str}");
+}
+
+#[test]
+fn time_reports_the_elapsed_time() {
+    let output = ShellRunner::new()
+        .with_stdin("time sleep 0\ntime echo hi | tr a-z A-Z && time false || echo failed\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "HI\nfailed\n");
+    let stderr_str = String::from_utf8(output.stderr).unwrap();
+    let times: Vec<_> = stderr_str
+        .lines()
+        .filter(|line| line.starts_with("real\t0m"))
+        .collect();
+    assert_eq!(times.len(), 3, "{stderr_str}");
+}
+
+#[test]
+fn echo_joins_arguments_with_a_custom_separator() {
+    let output = ShellRunner::new()
+        .with_stdin("echo -s , a b c\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "a,b,c\n");
+}
+
+#[test]
+fn pipelines_can_have_many_commands() {
+    let output = ShellRunner::new()
+        .with_stdin("printf 'b\\na\\nb\\nc\\n' | grep -v c | sort | uniq | wc -l\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str.trim_start(), "2\n");
+}
+
+#[test]
+fn external_commands_in_a_pipeline_run_at_the_same_time() {
+    // `yes` never exits on its own. It stops once `head` closes the pipe.
+    let output = ShellRunner::new()
+        .with_stdin("yes | cat | head -n 2\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "y\ny\n");
+}
+
+#[test]
+fn builtins_in_a_pipeline_stream_their_input() {
+    // `tr` must not wait for the end of `yes`, which never comes.
+    let output = ShellRunner::new()
+        .with_stdin("yes | tr y n | head -n 2\nyes | head -n 3 | tr y n | tail -n 1\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    output.assert_exit(0);
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "n\nn\nn\n");
+}
+
+#[test]
+fn and_or_depend_on_the_status_of_the_whole_pipeline() {
+    let output = ShellRunner::new()
+        .with_stdin(
+            "echo foo | grep foo && echo found\necho foo | grep bar && echo found || echo missing\n",
+        )
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "foo\nfound\nmissing\n");
+}
+
+#[test]
+fn redirection_without_command_truncates_the_file() {
+    let temp_file = generate_temp_file_name();
+    fs::write(&temp_file, "content\n").unwrap();
+    let stdin = format!(
+        "> {0} && echo ok\ncat {0}\n< {0}.missing || echo failed\n",
+        temp_file.display()
+    );
+
+    let output = ShellRunner::new()
+        .with_stdin(&stdin)
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "ok\nfailed\n");
+    assert_eq!(fs::read_to_string(&temp_file).unwrap(), "");
+    fs::remove_file(temp_file).unwrap();
+}
+
+#[test]
+fn env_ignore_environment_clears_inherited_variables() {
+    let output = ShellRunner::new()
+        .with_stdin("env -i FOO=bar env\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "FOO=bar\n");
+}
+
+#[test]
+fn true_and_false_are_builtins() {
+    let output = ShellRunner::new()
+        .with_stdin("true && echo ok\nfalse && echo no\nfalse || echo yes\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "ok\nyes\n");
+}
+
+#[test]
+fn logout_exits_a_login_shell() {
+    let output = ShellRunner::new()
+        .with_stdin("logout\necho still running\n")
+        .with_args(&["--login"])
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+}
+
+#[test]
+fn logout_exits_with_the_last_status() {
+    let output = ShellRunner::new()
+        .with_stdin("false; logout\n")
+        .with_args(&["--login"])
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    output.assert_exit(1);
+}
+
+#[test]
+fn login_flags_after_the_script_belong_to_the_script() {
+    let script_path = generate_temp_file_name();
+    fs::write(&script_path, "logout || echo not a login shell\n").unwrap();
+    let output = ShellRunner::new()
+        .with_args(&[script_path.to_str().unwrap(), "-l"])
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    fs::remove_file(script_path).unwrap();
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "not a login shell\n");
+}
+
+#[test]
+fn logout_fails_in_other_shells() {
+    let output = ShellRunner::new()
+        .with_stdin("logout || echo still running\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "still running\n");
+}
+
+#[test]
+fn quotes_protect_expanded_values_from_globbing() {
+    let temp_dir = generate_temp_file_name();
+    fs::create_dir(&temp_dir).unwrap();
+    fs::write(temp_dir.join("a"), "").unwrap();
+    fs::write(temp_dir.join("b"), "").unwrap();
+    let stdin = format!("cd {}\nx='*'\necho $x\necho \"$x\"\n", temp_dir.display());
+
+    let output = ShellRunner::new()
+        .with_stdin(&stdin)
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "a b\n*\n");
+    fs::remove_dir_all(temp_dir).unwrap();
+}
+
+#[test]
+fn braces_expand_to_several_words() {
+    let output = ShellRunner::new()
+        .with_stdin("echo file{1,2,3}.txt '{a,b}'\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "file1.txt file2.txt file3.txt {a,b}\n");
+}
+
+#[test]
+fn braces_expand_sequences() {
+    let output = ShellRunner::new()
+        .with_stdin("echo {1..5} {c..a} {0..10..5} {1..x}\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "1 2 3 4 5 c b a 0 5 10 {1..x}\n");
+}
+
+#[test]
+fn shell_level_is_incremented() {
+    let output = ShellRunner::new()
+        .with_env("SHLVL", "3")
+        .with_stdin("echo $SHLVL\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "4\n");
+}
+
+#[test]
+fn repeat_runs_a_command_several_times() {
+    let output = ShellRunner::new()
+        .with_stdin(
+            "repeat 3 echo x\nrepeat 2 echo '$NOT_EXPANDED'\nrepeat 2 sh -c 'echo ext'\nrepeat x echo y || echo failed\n",
+        )
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    // The command isn't expanded again for each run.
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout_str,
+        "x\nx\nx\n$NOT_EXPANDED\n$NOT_EXPANDED\next\next\nfailed\n"
+    );
+    let stderr_str = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr_str.contains("repeat: x: numeric argument required"),
+        "{stderr_str}"
+    );
+}
+
+#[test]
+fn help_lists_builtins() {
+    let output = ShellRunner::new()
+        .with_stdin("help\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout_str.contains("  cd "), "{stdout_str}");
+    assert!(stdout_str.contains("  exit "), "{stdout_str}");
+}
+
+#[test]
+fn pipestatus_contains_the_status_of_each_command() {
+    let output = ShellRunner::new()
+        .with_stdin(
+            "true | false | true\necho $PIPESTATUS ${PIPESTATUS[1]}\nprintenv PIPESTATUS || echo unset\n",
+        )
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "0 1 0 1\nunset\n");
+}
+
+#[test]
+fn pushd_and_popd_use_a_directory_stack() {
+    let output = ShellRunner::new()
+        .with_stdin("pushd examples; dirs; popd; pwd\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    // `pushd` and `popd` print the stack like `dirs`.
+    let cwd = std::env::current_dir().unwrap();
+    let examples = cwd.join("examples");
+    let stack = format!("{} {}\n", examples.display(), cwd.display());
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout_str,
+        format!("{stack}{stack}{0}\n{0}\n", cwd.display())
+    );
+}
+
+#[test]
+fn popd_with_empty_stack_is_an_error() {
+    let output = ShellRunner::new()
+        .with_stdin("popd || echo failed\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "failed\n");
+}
+
+#[test]
+fn cd_to_a_bookmark_from_another_directory() {
+    let bookmarks_path = generate_temp_file_name();
+    let output = ShellRunner::new()
+        .with_env("BOOKMARKS_PATH", &bookmarks_path)
+        .with_stdin("cd examples\nbookmark ex\ncd /\ncd @ex\npwd\ncd @missing || echo failed\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let examples = std::env::current_dir().unwrap().join("examples");
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, format!("{}\nfailed\n", examples.display()));
+    let bookmarks = fs::read_to_string(bookmarks_path).unwrap();
+    assert_eq!(bookmarks, format!("ex\t{}\n", examples.display()));
+}
+
+#[test]
+fn history_shows_the_last_entries_numbered() {
+    let history_path = generate_temp_file_name();
+    fs::write(&history_path, "echo 1\necho 2\necho 3\n").unwrap();
+    let output = ShellRunner::new()
+        .with_env("HISTORY_PATH", &history_path)
+        .with_stdin("history 2\nhistory x || echo failed\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    fs::remove_file(history_path).unwrap();
+    // The `history` command itself is already in the history when it runs.
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "    3  echo 3\n    4  history 2\nfailed\n");
+}
+
+#[test]
+fn history_search_shows_matching_entries() {
+    let history_path = generate_temp_file_name();
+    fs::write(&history_path, "cargo build\necho hi\ncargo test\n").unwrap();
+    let output = ShellRunner::new()
+        .with_env("HISTORY_PATH", &history_path)
+        .with_stdin("history --search argo\nhistory --search || echo failed\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    fs::remove_file(history_path).unwrap();
+    // The search itself is in the history too, and matches.
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout_str,
+        "    1  cargo build\n    3  cargo test\n    4  history --search argo\nfailed\n"
+    );
+}
+
+#[test]
+fn history_json_escapes_commands() {
+    let history_path = generate_temp_file_name();
+    fs::write(&history_path, "echo \"hi\"\nprintf 'a\\b'\n").unwrap();
+    let output = ShellRunner::new()
+        .with_env("HISTORY_PATH", &history_path)
+        .with_stdin("history --json\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    fs::remove_file(history_path).unwrap();
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout_str,
+        concat!(
+            r#"[{"index": 1, "command": "echo \"hi\""}, "#,
+            r#"{"index": 2, "command": "printf 'a\\b'"}, "#,
+            r#"{"index": 3, "command": "history --json"}]"#,
+            "\n"
+        )
+    );
+}
+
+#[test]
+fn history_c_clears_the_history() {
+    let history_path = generate_temp_file_name();
+    fs::write(&history_path, "echo 1\necho 2\n").unwrap();
+    let output = ShellRunner::new()
+        .with_env("HISTORY_PATH", &history_path)
+        .with_stdin("history -c\nhistory\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    fs::remove_file(history_path).unwrap();
+    // Only the `history` command run after clearing is left.
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "    1  history\n");
+}
+
+#[test]
+fn consecutive_duplicates_are_not_added_to_the_history() {
+    let output = ShellRunner::new()
+        .with_stdin("true\ntrue\ntrue\nhistory\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "    1  true\n    2  history\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn output_of_the_last_command_appears_while_it_runs() {
+    let mut child = ShellRunner::new()
+        .with_stdin("sh -c 'echo first; sleep 1; echo second'\n")
+        .example("block6")
+        .spawn();
+
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut first = String::new();
+    stdout.read_line(&mut first).unwrap();
+    let first_seen = Instant::now();
+    let mut second = String::new();
+    stdout.read_line(&mut second).unwrap();
+    let between = first_seen.elapsed();
+    child.wait().unwrap();
+
+    assert_eq!((first.as_str(), second.as_str()), ("first\n", "second\n"));
+    // If the shell collected the output until the command exits, both lines would arrive together.
+    assert!(between >= Duration::from_millis(500), "{between:?}");
+}
+
+#[cfg(unix)]
+#[test]
+fn closed_stdout_is_reported_without_stopping_the_shell() {
+    let mut child = ShellRunner::new()
+        .with_stdin("sleep 0.5; echo hi\necho still running >&2\n")
+        .example("block6")
+        .spawn();
+    // Nobody reads the output of the shell anymore, like in `rush | head -n 0`.
+    drop(child.stdout.take());
+
+    let output = child.wait_with_output().unwrap();
+    let stderr_str = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr_str.contains("Broken pipe"), "{stderr_str}");
+    assert!(stderr_str.ends_with("still running\n"), "{stderr_str}");
+}
+
+#[test]
+fn errors_are_printed_to_stderr() {
+    let output = ShellRunner::new()
+        .with_stdin("this-command-does-not-exist\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    // Like in bash, the exit code of a command which doesn't exist is 127.
+    output.assert_exit(127);
+    let stderr_str = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(
+        stderr_str,
+        "this-command-does-not-exist: command not found\n"
+    );
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn variables_from_the_environment_are_expanded() {
+    let output = ShellRunner::new()
+        .with_env("GREETING", "hello world")
+        .with_stdin("echo \"$GREETING\"\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "hello world\n");
+}
+
+#[test]
+fn shell_exits_at_the_end_of_its_input() {
+    let start = std::time::Instant::now();
+    let output = ShellRunner::new()
+        .with_stdin("echo done\n")
+        .example("block6")
+        // Only a safety net, in case the shell keeps waiting for input.
+        .kill_after(Duration::from_secs(60))
+        .run();
+
+    // The shell wasn't killed, it exited on its own right away.
+    output.assert_exit(0);
+    let elapsed = start.elapsed();
+    assert!(
+        elapsed < Duration::from_secs(3),
+        "the shell took {elapsed:?} to exit"
+    );
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "done\n");
+}
+
+#[test]
+fn here_string_is_the_stdin_of_the_command() {
+    let output = ShellRunner::new()
+        .with_stdin("wc -c <<< \"hello world\"\ntr a-z A-Z <<< $HOME\n")
+        .with_env("HOME", "/home/ferris")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    // The here-string ends with a newline.
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str.trim_start(), "12\n/HOME/FERRIS\n");
+}
+
+#[test]
+fn here_document_is_the_stdin_of_the_command() {
+    let output = ShellRunner::new()
+        .with_stdin(
+            "x=world\ncat << EOF\nhello \"$x\"\n  \\$x $((1 + 2))\nEOF\ntr a-z A-Z <<- END\n\tindented $x\n\tEND\n",
+        )
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "hello \"world\"\n  $x 3\nINDENTED WORLD\n");
+}
+
+#[test]
+fn here_document_with_a_quoted_delimiter_is_not_expanded() {
+    let output = ShellRunner::new()
+        .with_stdin("x=world\ncat << 'EOF'\n$x `pwd` \\$x\nEOF\ncat << \"EOF\"\n$((1 + 2))\nEOF\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "$x `pwd` \\$x\n$((1 + 2))\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn stdout_and_stderr_are_combined() {
+    let both = generate_temp_file_name();
+    let stdout_only = generate_temp_file_name();
+    let cmd = "sh -c 'echo out; echo err >&2'";
+    let stdin = format!(
+        "{cmd} &> {}\n{cmd} 2>&1 > {}\necho to stderr >&2\n",
+        both.display(),
+        stdout_only.display()
+    );
+    let output = ShellRunner::new()
+        .with_stdin(&stdin)
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    assert_eq!(fs::read_to_string(&both).unwrap(), "out\nerr\n");
+    // stderr goes to stdout before stdout is redirected to the file.
+    assert_eq!(fs::read_to_string(&stdout_only).unwrap(), "out\n");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "err\n");
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "to stderr\n");
+    fs::remove_file(both).unwrap();
+    fs::remove_file(stdout_only).unwrap();
+}
+
+#[test]
+fn one_is_the_file_descriptor_of_stdout() {
+    let file = generate_temp_file_name();
+    let stdin = format!(
+        "echo hi 1>{}\necho err 1>&2\necho out >&1\n",
+        file.display()
+    );
+    let output = ShellRunner::new()
+        .with_stdin(&stdin)
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    assert_eq!(fs::read_to_string(&file).unwrap(), "hi\n");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "out\n");
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "err\n");
+    fs::remove_file(file).unwrap();
+}
+
+#[test]
+fn redirection_without_a_file_is_a_syntax_error() {
+    let output = ShellRunner::new()
+        .with_stdin("echo hi > || echo failed\necho hi >\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    output.assert_exit(2);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Error: syntax error near unexpected token `||`\n\
+         Error: syntax error near unexpected token `newline`\n"
+    );
+}
+
+#[test]
+fn arithmetic_is_expanded() {
+    let output = ShellRunner::new()
+        .with_stdin("x=4\necho $((2 + 3 * $x))\necho $((1 / 0)) || echo failed\n")
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "14\nfailed\n");
+    let stderr_str = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr_str, "Error: division by zero\n");
+}
+
+#[test]
+fn command_output_is_substituted() {
+    let stdin = "echo $(echo hi)\necho \"two  spaces: $(echo 'a  b')\"\necho `echo back`ticks\necho $(echo $(echo nested))\n";
+    let output = ShellRunner::new()
+        .with_stdin(stdin)
+        .example("block6")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "hi\ntwo  spaces: a  b\nbackticks\nnested\n");
+}
@@ -3,5 +3,8 @@ mod block2;
 mod block3;
 mod block4;
 mod block5;
+mod block6;
+mod parser;
+mod shell;
 
 mod utils;
@@ -0,0 +1,29 @@
+use rush::{parse, Cmd, Element};
+
+#[test]
+fn parser_is_available_as_a_library() {
+    let chains = parse("echo hello | wc -c; exit 1").unwrap();
+
+    assert_eq!(chains.len(), 2);
+    assert_eq!(
+        chains[0].elements,
+        [
+            Element::Cmd(Cmd {
+                binary: "echo",
+                args: vec!["hello"]
+            }),
+            Element::Pipe,
+            Element::Cmd(Cmd {
+                binary: "wc",
+                args: vec!["-c"]
+            }),
+        ]
+    );
+    assert_eq!(
+        chains[1].elements,
+        [Element::Cmd(Cmd {
+            binary: "exit",
+            args: vec!["1"]
+        })]
+    );
+}
@@ -0,0 +1,60 @@
+//! Tests for the shell in `src/main.rs`, not one of the examples.
+
+use std::time::Duration;
+
+use crate::utils::{AssertOutput, ShellRunner};
+
+const SHELL_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[test]
+fn cd_changes_the_directory() {
+    let output = ShellRunner::new()
+        .with_stdin("cd examples; pwd; exit\n")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    output.assert_exit(0);
+    let examples = std::env::current_dir().unwrap().join("examples");
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, format!("{}\n", examples.display()));
+}
+
+#[test]
+fn cd_to_an_absolute_path() {
+    let output = ShellRunner::new()
+        .with_stdin("cd /; pwd; cd /does/not/exist; pwd; exit\n")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    output.assert_exit(0);
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout_str, "/\n/\n");
+    assert!(!output.stderr.is_empty());
+}
+
+#[test]
+fn cd_to_a_relative_path() {
+    let output = ShellRunner::new()
+        .with_stdin("cd examples/block6; cd ../..; pwd; cd ./examples; pwd; exit\n")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    output.assert_exit(0);
+    let cwd = std::env::current_dir().unwrap();
+    let stdout_str = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout_str,
+        format!("{}\n{}\n", cwd.display(), cwd.join("examples").display())
+    );
+}
+
+#[test]
+fn exit_stops_the_shell() {
+    let output = ShellRunner::new()
+        .with_stdin("exit\necho unreachable\n")
+        .kill_after(SHELL_TIMEOUT)
+        .run();
+
+    output.assert_exit(0);
+    assert!(output.stdout.is_empty());
+}
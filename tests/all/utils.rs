@@ -1,22 +1,70 @@
 use std::{
-    io::Write,
-    process::{Child, Command, Output, Stdio},
-    thread,
-    time::Duration,
+    env,
+    ffi::{OsStr, OsString},
+    fs,
+    io::{Read, Write},
+    path::PathBuf,
+    process::{self, Child, Command, ExitStatus, Output, Stdio},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
+/// Generate a unique path in the temporary directory.
+/// The file itself is not created.
+pub fn generate_temp_file_name() -> PathBuf {
+    let temp_dir = env::temp_dir();
+    let pid = process::id();
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_nanos();
+    let file_name = format!("temp_file_{}_{}", pid, timestamp);
+    temp_dir.join(file_name)
+}
+
+/// Assertions on the `Output` of a shell, with helpful messages when they fail.
+pub trait AssertOutput {
+    /// Assert that the shell exited with `code`.
+    ///
+    /// On mismatch, the panic message includes stdout and stderr of the shell.
+    fn assert_exit(&self, code: i32) -> &Self;
+}
+
+impl AssertOutput for Output {
+    fn assert_exit(&self, code: i32) -> &Self {
+        if self.status.code() != Some(code) {
+            panic!(
+                "expected exit code {code}, but the shell exited with {}\n\
+                 --- stdout ---\n{}\n--- stderr ---\n{}",
+                self.status,
+                String::from_utf8_lossy(&self.stdout),
+                String::from_utf8_lossy(&self.stderr),
+            );
+        }
+        self
+    }
+}
+
 pub struct ShellRunner<'a> {
     stdin: Option<&'a str>,
+    args: &'a [&'a str],
+    envs: Vec<(&'a str, OsString)>,
     kill_after: Option<Duration>,
     example: Option<&'a str>,
+    /// The history file of the shell, unless the test sets `HISTORY_PATH` itself.
+    /// Otherwise, the shells would write their history into the repository.
+    history_path: PathBuf,
 }
 
 impl<'a> ShellRunner<'a> {
     pub fn new() -> Self {
         Self {
             stdin: None,
+            args: &[],
+            envs: Vec::new(),
             kill_after: None,
             example: None,
+            history_path: generate_temp_file_name(),
         }
     }
 
@@ -25,41 +73,84 @@ impl<'a> ShellRunner<'a> {
         self
     }
 
+    /// Arguments passed to the shell.
+    pub fn with_args(mut self, args: &'a [&'a str]) -> Self {
+        self.args = args;
+        self
+    }
+
+    /// Set an environment variable for the shell only.
+    ///
+    /// Unlike `std::env::set_var` in the test, this doesn't affect other tests running in parallel.
+    pub fn with_env(mut self, key: &'a str, value: impl AsRef<OsStr>) -> Self {
+        self.envs.push((key, value.as_ref().to_owned()));
+        self
+    }
+
+    /// Run an example instead of the shell in `src/main.rs`.
     pub fn example(mut self, example: &'a str) -> Self {
         self.example = Some(example);
         self
     }
 
-    /// Wait duration and kill the command afterwards.
-    /// Useful to test commands that don't exit on their own.
+    /// Wait at most `duration` for the shell to exit and kill it afterwards.
+    ///
+    /// Shells which exit at the end of their input finish as soon as they are done.
+    /// The timeout is needed for shells or commands that don't exit on their own.
     pub fn kill_after(mut self, duration: Duration) -> Self {
         self.kill_after = Some(duration);
         self
     }
 
+    /// Run the shell and collect its exit status, stdout and stderr.
     pub fn run(&self) -> Output {
         let mut child = self.run_shell();
         self.write_stdin(&mut child);
-        self.wait(child)
+        let output = self.wait(child);
+        // The shell may not have written a history, then there is nothing to remove.
+        let _ = fs::remove_file(&self.history_path);
+        output
+    }
+
+    /// Start the shell and write its stdin, but don't wait for it.
+    ///
+    /// This allows to read the output while the shell is still running.
+    pub fn spawn(&self) -> Child {
+        let mut child = self.run_shell();
+        self.write_stdin(&mut child);
+        child
     }
 
     fn run_shell(&self) -> Child {
-        let mut command = Command::new("cargo");
-        command.arg("run");
-        if let Some(example) = self.example {
-            command.args(["--example", example]);
-        }
-        command.stdin(Stdio::piped()).stdout(Stdio::piped());
+        let mut command = match self.example {
+            Some(example) => {
+                let mut command = Command::new("cargo");
+                // `--quiet` keeps the build messages of cargo out of stderr.
+                command.args(["run", "--quiet", "--example", example, "--"]);
+                command
+            }
+            // Cargo builds the shell in `src/main.rs` before running integration tests.
+            None => Command::new(env!("CARGO_BIN_EXE_rush")),
+        };
+        command.args(self.args);
+        // Set first, so `with_env("HISTORY_PATH", ...)` overrides it.
+        command.env("HISTORY_PATH", &self.history_path);
+        command.envs(self.envs.iter().map(|(key, value)| (key, value)));
+        command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
 
         command.spawn().unwrap()
     }
 
     fn write_stdin(&self, child: &mut Child) {
-        if let Some(stdin) = self.stdin {
+        // Dropping stdin closes it, so the shell sees the end of its input and can exit.
+        let child_stdin = child.stdin.take();
+        if let (Some(stdin), Some(mut child_stdin)) = (self.stdin, child_stdin) {
             // Use scoped threads to avoid cloning stdin.
             thread::scope(|s| {
                 s.spawn(|| {
-                    let mut child_stdin = child.stdin.take().unwrap();
                     child_stdin.write_all(stdin.as_bytes()).unwrap();
                     child_stdin.flush().unwrap();
                 });
@@ -68,12 +159,44 @@ impl<'a> ShellRunner<'a> {
     }
 
     fn wait(&self, mut child: Child) -> Output {
-        if let Some(duration) = self.kill_after {
-            std::thread::sleep(duration);
+        // Read the output while waiting. The shell blocks once a pipe is full.
+        let stdout = child.stdout.take().map(read_in_background);
+        let stderr = child.stderr.take().map(read_in_background);
+
+        let status = match self.kill_after {
+            Some(duration) => wait_timeout(&mut child, duration),
+            None => child.wait().unwrap(),
+        };
+
+        Output {
+            status,
+            stdout: stdout.map_or_else(Vec::new, |reader| reader.join().unwrap()),
+            stderr: stderr.map_or_else(Vec::new, |reader| reader.join().unwrap()),
+        }
+    }
+}
+
+/// Read everything from `pipe` on another thread.
+fn read_in_background(mut pipe: impl Read + Send + 'static) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        pipe.read_to_end(&mut buffer).unwrap();
+        buffer
+    })
+}
+
+/// Wait for `child` to exit, but kill it once `duration` has passed.
+fn wait_timeout(child: &mut Child, duration: Duration) -> ExitStatus {
+    let start = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            return status;
+        }
+        if start.elapsed() >= duration {
             child.kill().unwrap();
             println!("Timeout: killed the shell after {:?}", duration);
+            return child.wait().unwrap();
         }
-
-        child.wait_with_output().unwrap()
+        thread::sleep(Duration::from_millis(10));
     }
 }